@@ -0,0 +1,133 @@
+use std::time::Duration;
+
+use rodio::Source;
+
+const SAMPLE_RATE: u32 = 44100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    White,
+    Pink,
+    Brown,
+}
+
+/// An endless mono noise, generated on the fly
+#[derive(Debug, Clone)]
+pub struct Noise {
+    color: Color,
+    seed: u32,
+    // pink filter state
+    b: [f32; 7],
+    // brown integrator state
+    last: f32,
+}
+
+impl Noise {
+    pub fn new(color: Color) -> Self {
+        Noise { color, seed: 0x2545_f491, b: [0.; 7], last: 0. }
+    }
+
+    // xorshift32, good enough for noise and no extra dependency
+    fn white(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed as f32 / u32::MAX as f32 * 2. - 1.
+    }
+}
+
+impl Iterator for Noise {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let white = self.white();
+        let sample = match self.color {
+            Color::White => white * 0.3,
+            // Paul Kellet's refined method
+            Color::Pink => {
+                let b = &mut self.b;
+                b[0] = 0.99886 * b[0] + white * 0.0555179;
+                b[1] = 0.99332 * b[1] + white * 0.0750759;
+                b[2] = 0.96900 * b[2] + white * 0.153852;
+                b[3] = 0.86650 * b[3] + white * 0.3104856;
+                b[4] = 0.55000 * b[4] + white * 0.5329522;
+                b[5] = -0.7616 * b[5] - white * 0.0168980;
+                let pink = b.iter().sum::<f32>() + white * 0.5362;
+                b[6] = white * 0.115926;
+                pink * 0.05
+            },
+            Color::Brown => {
+                self.last = (self.last + 0.02 * white) / 1.02;
+                self.last * 3.5
+            },
+        };
+        Some(sample)
+    }
+}
+
+impl Source for Noise {
+    fn current_frame_len(&self) -> Option<usize> { None }
+    fn channels(&self) -> u16 { 1 }
+    fn sample_rate(&self) -> u32 { SAMPLE_RATE }
+    fn total_duration(&self) -> Option<Duration> { None }
+}
+
+/// A clock ticking once a second, alternating between a high and a low tick
+#[derive(Debug, Clone, Default)]
+pub struct Tick {
+    sample: u64,
+}
+
+impl Tick {
+    pub fn new() -> Self {
+        Tick::default()
+    }
+}
+
+impl Iterator for Tick {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let second = self.sample / SAMPLE_RATE as u64;
+        let t = (self.sample % SAMPLE_RATE as u64) as f32 / SAMPLE_RATE as f32;
+        self.sample += 1;
+
+        // a 20ms decaying sine burst at the start of every second
+        if t > 0.02 {
+            return Some(0.);
+        }
+        let pitch = if second.is_multiple_of(2) { 1800. } else { 1400. };
+        let envelope = (-t * 250.).exp();
+        Some((t * pitch * std::f32::consts::TAU).sin() * envelope * 0.5)
+    }
+}
+
+impl Source for Tick {
+    fn current_frame_len(&self) -> Option<usize> { None }
+    fn channels(&self) -> u16 { 1 }
+    fn sample_rate(&self) -> u32 { SAMPLE_RATE }
+    fn total_duration(&self) -> Option<Duration> { None }
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use super::{Color, Noise, Tick, SAMPLE_RATE};
+
+    #[test]
+    fn noise_test() {
+        for color in [Color::White, Color::Pink, Color::Brown] {
+            let samples: Vec<f32> = Noise::new(color).take(SAMPLE_RATE as usize).collect();
+            assert!(samples.iter().all(|s| s.abs() <= 1.), "{:?} noise clips", color);
+            assert!(samples.iter().any(|s| *s != 0.), "{:?} noise is silent", color);
+        }
+    }
+
+    #[test]
+    fn tick_test() {
+        let samples: Vec<f32> = Tick::new().take(SAMPLE_RATE as usize * 2).collect();
+        // silent between the ticks
+        assert!(samples[SAMPLE_RATE as usize / 2..SAMPLE_RATE as usize].iter().all(|s| *s == 0.));
+        assert!(samples[..SAMPLE_RATE as usize / 100].iter().any(|s| *s != 0.));
+        assert!(samples[SAMPLE_RATE as usize..SAMPLE_RATE as usize * 101 / 100].iter().any(|s| *s != 0.));
+    }
+}
@@ -0,0 +1,507 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener},
+    os::unix::{fs::PermissionsExt, net::UnixListener},
+    path::Path,
+    thread,
+};
+
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    cli_config, foreground::parse_duration, history, stats, with_task,
+    client::{Client, Refused},
+    config::{Config, Http},
+    protocol::{PAUSE, RESUME, SKIP},
+};
+
+/// bodies and headers past that are refused
+const MAX_REQUEST: usize = 64 * 1024;
+
+/// The HTTP api, a gateway to the sockets of the daemon it runs in
+#[derive(Debug, Clone)]
+pub struct Api {
+    pub client: Client,
+    /// without one every request is refused, unless `open`
+    pub token: Option<String>,
+    /// a unix socket without a token, only its owner can connect to it
+    pub open: bool,
+    /// the `Host` headers accepted, any when empty as on a unix socket where
+    /// no web page can rebind a name to
+    pub hosts: Vec<String>,
+    /// the origins a browser is allowed to read the answers from
+    pub origins: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+
+    fn query(&self, name: &str) -> Option<&str> {
+        self.query.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+}
+
+/// What `POST /start` takes, every field is optional and defaults like `comodo run`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Start {
+    /// as `25m`, `90s` or `25:00`
+    pub focus: Option<String>,
+    pub rest: Option<String>,
+    pub long_rest: Option<String>,
+    pub number: Option<u8>,
+    pub task: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl Start {
+    pub fn config(self) -> Result<Config, String> {
+        let durations = [(self.focus, "25m"), (self.rest, "5m"), (self.long_rest, "15m")]
+            .map(|(duration, default)| parse_duration(duration.as_deref().unwrap_or(default)));
+        let [focus, rest, long_rest] = match durations {
+            [Ok(focus), Ok(rest), Ok(long_rest)] => [focus, rest, long_rest],
+            [Err(e), ..] | [_, Err(e), _] | [.., Err(e)] => return Err(e),
+        };
+        let mut config = cli_config(self.number.unwrap_or(4), focus, rest);
+        config.comodo.big_rest = long_rest;
+        with_task(&mut config, self.task, self.tags)?;
+        Ok(config)
+    }
+}
+
+enum Reply {
+    Json(u16, serde_json::Value),
+    /// the server-sent events of `/events`, of the timer of the client
+    Events(Client),
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+fn error(status: u16, message: impl Into<String>) -> Reply {
+    Reply::Json(status, json!({ "error": message.into() }))
+}
+
+// percent-decoding is all the query strings of the api need
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 2;
+                    },
+                    None => out.push(b'%'),
+                }
+            },
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn parse(reader: &mut impl BufRead) -> Result<Request, String> {
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(format!("invalid request line {:?}", line.trim_end()));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query.split('&').filter(|p| !p.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(name), decode(value))
+        })
+        .collect();
+
+    let mut headers = vec![];
+    let mut size = line.len();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            return Err(String::from("the headers never ended"));
+        }
+        size += line.len();
+        if size > MAX_REQUEST {
+            return Err(String::from("the headers are too large"));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let mut request = Request { method: method.to_string(), path: path.to_string(), query, headers, body: vec![] };
+    let length = request.header("content-length").map(|l| l.parse::<usize>()).transpose()
+        .map_err(|e| format!("invalid content length: {}", e))?
+        .unwrap_or(0);
+    if length > MAX_REQUEST {
+        return Err(String::from("the body is too large"));
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body).map_err(|e| e.to_string())?;
+    Ok(request)
+}
+
+impl Api {
+    pub fn new(socket_path: &str, state_path: &str, status_path: &str, token: Option<String>) -> Self {
+        Api {
+            client: Client::new(socket_path, state_path, status_path),
+            token,
+            open: false,
+            hosts: vec![],
+            origins: vec![],
+        }
+    }
+
+    fn authorized(&self, request: &Request) -> bool {
+        let Some(token) = &self.token else { return self.open };
+        let bearer = request.header("authorization").and_then(|a| a.strip_prefix("Bearer "));
+        bearer.or(request.query("token")) == Some(token.as_str())
+    }
+
+    /// the client of `timer`, the default one when there is none or it is empty
+    pub fn for_timer(&self, timer: Option<&str>) -> Client {
+        match timer.filter(|timer| !timer.is_empty()) {
+            Some(timer) => self.client.clone().timer(timer),
+            None => self.client.clone(),
+        }
+    }
+
+    fn control(client: &Client, command: &[u8]) -> Reply {
+        match client.send(command) {
+            Ok(()) => Reply::Json(200, json!({ "ok": true })),
+            Err(e) => error(500, e),
+        }
+    }
+
+    fn start_reply(client: &Client, body: &[u8]) -> Reply {
+        let start: Start = if body.iter().all(u8::is_ascii_whitespace) {
+            Start::default()
+        } else {
+            match serde_json::from_slice(body) {
+                Ok(start) => start,
+                Err(e) => return error(400, format!("invalid body: {}", e)),
+            }
+        };
+        match start.config().map(|config| client.start(&config)) {
+            Ok(Ok(())) => Reply::Json(200, json!({ "ok": true })),
+            Ok(Err(e @ Refused::Running)) => error(409, e.to_string()),
+            Ok(Err(e @ Refused::Unreachable(_))) => error(500, e.to_string()),
+            Err(e) => error(400, e),
+        }
+    }
+
+    fn history(&self, request: &Request) -> Reply {
+        let since = match request.query("since").map(stats::parse_date).transpose() {
+            Ok(since) => since,
+            Err(e) => return error(400, e),
+        };
+        let records = history::path().map(|path| history::read(&path)).unwrap_or(Ok(vec![]));
+        match records {
+            Ok(records) => {
+                let records: Vec<_> = records.into_iter()
+                    .filter(|r| since.is_none() || stats::day_of(r) >= since)
+                    .collect();
+                Reply::Json(200, json!(records))
+            },
+            Err(e) => error(500, e),
+        }
+    }
+
+    fn route(&self, request: &Request) -> Reply {
+        if !self.authorized(request) {
+            return error(401, "missing or wrong token");
+        }
+        // `?timer=<name>` for another timer than the default one
+        let client = self.for_timer(request.query("timer"));
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/status") => match client.status() {
+                Ok(snapshot) => Reply::Json(200, json!(snapshot)),
+                Err(e) => error(500, e),
+            },
+            ("GET", "/history") => self.history(request),
+            ("GET", "/events") => Reply::Events(client),
+            ("POST", "/start") => Api::start_reply(&client, &request.body),
+            ("POST", "/pause") => Api::control(&client, &PAUSE),
+            ("POST", "/resume") => Api::control(&client, &RESUME),
+            ("POST", "/skip") => Api::control(&client, &SKIP),
+            (_, "/status" | "/history" | "/events" | "/start" | "/pause" | "/resume" | "/skip") => error(405, "method not allowed"),
+            _ => error(404, "not found"),
+        }
+    }
+
+    // a page of another site resolving its own name to loopback gets turned away here
+    fn host_allowed(&self, request: &Request) -> bool {
+        self.hosts.is_empty() || request.header("host").is_some_and(|host| self.hosts.iter().any(|h| h.eq_ignore_ascii_case(host)))
+    }
+
+    // only the configured origins get to read the answers from a browser
+    fn cors(&self, request: &Request) -> String {
+        match request.header("origin").filter(|origin| self.origins.iter().any(|o| o == origin)) {
+            Some(origin) => format!("Access-Control-Allow-Origin: {}\r\nVary: Origin\r\n\
+                                     Access-Control-Allow-Headers: Authorization, Content-Type\r\n\
+                                     Access-Control-Allow-Methods: GET, POST, OPTIONS\r\n", origin),
+            None => String::new(),
+        }
+    }
+
+    /// every status the daemon sends, as a `status` event, with a `phase`
+    /// event whenever the session moves on to another phase
+    fn events(client: &Client, stream: &mut impl Write, cors: &str) -> Result<(), String> {
+        let snapshots = client.subscribe()?;
+        let header = format!("HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+                              {}Connection: close\r\n\r\n", cors);
+        stream.write_all(header.as_bytes()).map_err(|e| e.to_string())?;
+        let mut last = None;
+        for snapshot in snapshots {
+            let line = serde_json::to_string(&snapshot).map_err(|e| e.to_string())?;
+            let phase = (snapshot.running, snapshot.phase, snapshot.cycle);
+            let mut event = String::new();
+            if last.is_some_and(|last| last != phase) {
+                event += &format!("event: phase\ndata: {}\n\n", line);
+            }
+            last = Some(phase);
+            event += &format!("event: status\ndata: {}\n\n", line);
+            // a client gone is the end of the stream
+            stream.write_all(event.as_bytes()).and_then(|_| stream.flush()).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    pub fn handle<S: Read + Write>(&self, mut stream: S) {
+        let request = parse(&mut BufReader::new(&mut stream));
+        let reply = match &request {
+            Ok(request) if !self.host_allowed(request) => error(403, "unexpected host"),
+            // what browsers ask before sending the token along
+            Ok(request) if request.method == "OPTIONS" => Reply::Json(204, serde_json::Value::Null),
+            Ok(request) => self.route(request),
+            Err(e) => error(400, e.clone()),
+        };
+        let cors = request.as_ref().map(|request| self.cors(request)).unwrap_or_default();
+        let (status, body) = match reply {
+            Reply::Events(client) => {
+                if let Err(e) = Api::events(&client, &mut stream, &cors) {
+                    eprintln!("event stream closed: {}", e);
+                }
+                return;
+            },
+            Reply::Json(204, _) => (204, String::new()),
+            Reply::Json(status, body) => (status, body.to_string()),
+        };
+        let response = format!("HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                                {}Connection: close\r\n\r\n{}",
+                               status, reason(status), body.len(), cors, body);
+        if let Err(e) = stream.write_all(response.as_bytes()) {
+            eprintln!("could not answer an http request: {}", e);
+        }
+    }
+}
+
+/// Binds `http.listen` and serves the api from a thread, a connection each
+pub fn spawn(http: &Http, mut api: Api) -> Result<(), String> {
+    api.token = api.token.filter(|token| !token.is_empty());
+    api.origins = http.origins.clone();
+    if http.listen.contains('/') {
+        let path = Path::new(&http.listen);
+        if path.exists() {
+            fs::remove_file(path).map_err(|e| format!("could not remove {}: {}", path.display(), e))?;
+        }
+        let listener = UnixListener::bind(path).map_err(|e| format!("could not listen on {}: {}", path.display(), e))?;
+        if api.token.is_none() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+                .map_err(|e| format!("could not restrict {}: {}", path.display(), e))?;
+            api.open = true;
+        }
+        thread::spawn(move || {
+            for stream in listener.incoming().map_while(Result::ok) {
+                let api = api.clone();
+                thread::spawn(move || api.handle(stream));
+            }
+        });
+    } else {
+        if api.token.is_none() {
+            return Err(String::from("the http api needs a token on tcp, set one in [http]"));
+        }
+        let address: SocketAddr = http.listen.parse()
+            .map_err(|e| format!("invalid http address {:?}: {}", http.listen, e))?;
+        // anyone on the network could drive the timer otherwise
+        if !address.ip().is_loopback() {
+            return Err(format!("the http api only listens on loopback, not on {}", address));
+        }
+        let listener = TcpListener::bind(address).map_err(|e| format!("could not listen on {}: {}", address, e))?;
+        let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+        api.hosts = vec![format!("127.0.0.1:{}", port), format!("localhost:{}", port), format!("[::1]:{}", port)];
+        thread::spawn(move || {
+            for stream in listener.incoming().map_while(Result::ok) {
+                let api = api.clone();
+                thread::spawn(move || api.handle(stream));
+            }
+        });
+    }
+    println!("serving the http api on {}", http.listen);
+    Ok(())
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use std::{
+        fs, io::{BufRead, BufReader, Read, Write}, os::unix::{fs::PermissionsExt, net::{UnixListener, UnixStream}}, thread,
+    };
+    #[allow(unused_imports)]
+    use crate::{client::Client, config::Http, protocol::{to_timer, PAUSE}, snapshot::Snapshot};
+    #[allow(unused_imports)]
+    use super::{decode, parse, spawn, Api};
+
+    #[allow(dead_code)]
+    fn request(api: &Api, raw: &str) -> String {
+        let (mut client, server) = UnixStream::pair().unwrap();
+        client.write_all(raw.as_bytes()).unwrap();
+        api.handle(server);
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn parse_test() {
+        let raw = "POST /start?token=a%20b&x HTTP/1.1\r\nHost: localhost\r\ncontent-length: 13\r\n\r\n{\"number\": 2}";
+        let request = parse(&mut BufReader::new(raw.as_bytes())).unwrap();
+        assert_eq!((request.method.as_str(), request.path.as_str()), ("POST", "/start"));
+        assert_eq!(request.query("token"), Some("a b"));
+        assert_eq!(request.query("x"), Some(""));
+        assert_eq!(request.header("Content-Length"), Some("13"));
+        assert_eq!(request.body, b"{\"number\": 2}");
+
+        assert!(parse(&mut BufReader::new("\r\n".as_bytes())).is_err());
+        assert!(parse(&mut BufReader::new("GET / HTTP/1.1\r\nHost: x\r\n".as_bytes())).is_err());
+        assert_eq!(decode("%2Fa+b%zz%4"), "/a b%zz%4");
+    }
+
+    #[test]
+    fn route_test() {
+        let dir = std::env::temp_dir().join(format!("comodo-api-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let api = Api {
+            client: Client::new(dir.join("comodo.sock"), dir.join("state.sock"), dir.join("status.sock")),
+            token: Some(String::from("secret")),
+            open: false,
+            hosts: vec![],
+            origins: vec![],
+        };
+        let state = UnixListener::bind(&api.client.state_path).unwrap();
+        let status = UnixListener::bind(&api.client.status_path).unwrap();
+
+        assert!(request(&api, "GET /status HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+        assert!(request(&api, "GET /nope?token=secret HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404"));
+        assert!(request(&api, "GET /pause?token=secret HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 405"));
+        assert!(request(&api, "OPTIONS /pause HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 204"));
+        assert!(!request(&api, "OPTIONS /pause HTTP/1.1\r\nOrigin: http://evil.com\r\n\r\n").contains("Access-Control"));
+        let tcp = Api {
+            hosts: vec![String::from("127.0.0.1:7878"), String::from("localhost:7878")],
+            origins: vec![String::from("http://localhost:3000")],
+            ..api.clone()
+        };
+        assert!(request(&tcp, "GET /status?token=secret HTTP/1.1\r\nHost: evil.com:7878\r\n\r\n").starts_with("HTTP/1.1 403"));
+        assert!(request(&tcp, "GET /status?token=secret HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 403"));
+        let response = request(&tcp, "OPTIONS /pause HTTP/1.1\r\nHost: LOCALHOST:7878\r\nOrigin: http://localhost:3000\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 204"));
+        assert!(response.contains("Access-Control-Allow-Origin: http://localhost:3000\r\n"));
+        assert!(request(&Api { token: None, ..api.clone() }, "GET /status HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 401"));
+        assert!(request(&Api { token: None, open: true, ..api.clone() }, "GET /nope HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404"));
+
+        let response = request(&api, "POST /pause HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\n{\"ok\":true}"));
+        let mut command = vec![];
+        state.accept().unwrap().0.read_to_end(&mut command).unwrap();
+        assert_eq!(command, PAUSE);
+        request(&api, "POST /pause?token=secret&timer=tea HTTP/1.1\r\n\r\n");
+        let mut command = vec![];
+        state.accept().unwrap().0.read_to_end(&mut command).unwrap();
+        assert_eq!(command, to_timer("tea", &PAUSE));
+
+        // the daemon answers the status with a snapshot per tick
+        let daemon = thread::spawn(move || {
+            for _ in 0..2 {
+                let (mut stream, _) = status.accept().unwrap();
+                let snapshot = Snapshot { running: true, cycle: 2, ..Default::default() };
+                stream.write_all(format!("{}\n", serde_json::to_string(&snapshot).unwrap()).as_bytes()).unwrap();
+            }
+        });
+        let response = request(&api, "GET /status?token=secret HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        assert_eq!(serde_json::from_str::<Snapshot>(body).unwrap().cycle, 2);
+
+        let response = request(&api, "POST /start?token=secret HTTP/1.1\r\nContent-Length: 15\r\n\r\n{\"focus\": \"1m\"}");
+        assert!(response.starts_with("HTTP/1.1 409 Conflict\r\n"), "{}", response);
+        daemon.join().unwrap();
+
+        let response = request(&api, "POST /start?token=secret HTTP/1.1\r\nContent-Length: 13\r\n\r\n{\"focus\": 25}");
+        assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+        let response = request(&api, "POST /start?token=secret HTTP/1.1\r\nContent-Length: 16\r\n\r\n{\"focus\": \"25x\"}");
+        assert!(response.contains("invalid duration"), "{}", response);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn spawn_test() {
+        let http = |listen: &str| Http { listen: listen.to_string(), token: None, origins: vec![] };
+        let api = Api {
+            client: Client::new("/nonexistent", "/nonexistent", "/nonexistent"),
+            token: None,
+            open: false,
+            hosts: vec![],
+            origins: vec![],
+        };
+        assert!(spawn(&http("127.0.0.1:7878"), api.clone()).unwrap_err().contains("needs a token"));
+        assert!(spawn(&http("127.0.0.1:7878"), Api { token: Some(String::new()), ..api.clone() }).unwrap_err().contains("needs a token"));
+        // a unix socket goes without, only its owner can reach it
+        let dir = std::env::temp_dir().join(format!("comodo-api-spawn-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("http.sock");
+        spawn(&http(path.to_str().unwrap()), api.clone()).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        fs::remove_dir_all(dir).unwrap();
+        let api = Api { token: Some(String::from("secret")), ..api };
+        assert!(spawn(&http("0.0.0.0:7878"), api.clone()).unwrap_err().contains("loopback"));
+        assert!(spawn(&http("localhost"), api).unwrap_err().contains("invalid http address"));
+    }
+}
@@ -0,0 +1,157 @@
+use std::io::{stdout, Write};
+
+use ratatui::crossterm::terminal;
+
+use crate::{as_time, client::Client, protocol::DEFAULT_TIMER, snapshot::Snapshot};
+
+/// rows of a glyph at scale 1
+pub const HEIGHT: usize = 5;
+
+// 3x5 glyphs, every cell is drawn two columns wide so they come out about square
+const FONT: [(char, [&str; HEIGHT]); 11] = [
+    ('0', ["###", "# #", "# #", "# #", "###"]),
+    ('1', ["  #", "  #", "  #", "  #", "  #"]),
+    ('2', ["###", "  #", "###", "#  ", "###"]),
+    ('3', ["###", "  #", "###", "  #", "###"]),
+    ('4', ["# #", "# #", "###", "  #", "  #"]),
+    ('5', ["###", "#  ", "###", "  #", "###"]),
+    ('6', ["###", "#  ", "###", "# #", "###"]),
+    ('7', ["###", "  #", "  #", "  #", "  #"]),
+    ('8', ["###", "# #", "###", "# #", "###"]),
+    ('9', ["###", "# #", "###", "  #", "###"]),
+    (':', ["   ", " # ", "   ", " # ", "   "]),
+];
+
+/// `text` in block digits, a line per row, what the font lacks is left blank
+pub fn lines(text: &str, fill: char, scale: usize) -> Vec<String> {
+    let blank = ["   "; HEIGHT];
+    let gap = " ".repeat(scale);
+    (0..HEIGHT * scale).map(|row| {
+        text.chars()
+            .map(|c| FONT.iter().find(|(g, _)| *g == c).map_or(blank, |(_, rows)| *rows)[row / scale])
+            .map(|cells| cells.chars().flat_map(|cell| {
+                let cell = if cell == '#' { fill } else { ' ' };
+                std::iter::repeat_n(cell, 2 * scale)
+            }).collect::<String>())
+            .collect::<Vec<_>>()
+            .join(&gap)
+    }).collect()
+}
+
+/// columns `text` takes at `scale`
+pub fn width(text: &str, scale: usize) -> usize {
+    let glyphs = text.chars().count();
+    glyphs * 6 * scale + glyphs.saturating_sub(1) * scale
+}
+
+/// the largest scale `text` fits in `columns` by `rows` at, 1 at least
+pub fn fit(text: &str, columns: usize, rows: usize) -> usize {
+    (1..).take_while(|scale| *scale == 1 || (width(text, *scale) <= columns && HEIGHT * scale <= rows))
+        .last()
+        .unwrap_or(1)
+}
+
+/// The remaining time of `snapshot` as large as the terminal allows, under a
+/// line about the phase, painted with the color of the phase
+pub fn clock(snapshot: &Snapshot, columns: usize, rows: usize) -> String {
+    let caption = match snapshot.phase {
+        Some(phase) if snapshot.running => {
+            let task = snapshot.task.as_ref().map(|t| format!(" - {}", t)).unwrap_or_default();
+            format!("{} {}/{}{}{}", phase.name(), snapshot.cycle, snapshot.total, task,
+                    if snapshot.paused { " (paused)" } else { "" })
+        },
+        _ => String::from("no session running"),
+    };
+    let caption = match snapshot.timer() {
+        DEFAULT_TIMER => caption,
+        timer => format!("{}: {}", timer, caption),
+    };
+    let time = as_time(snapshot.remaining());
+    // the caption and a blank line go above
+    let scale = fit(&time, columns, rows.saturating_sub(2));
+    let pad = |len: usize| " ".repeat(columns.saturating_sub(len) / 2);
+    let (on, off) = match snapshot.color {
+        Some(rgb) => (format!("\x1b[38;2;{};{};{}m", rgb.0, rgb.1, rgb.2), "\x1b[0m"),
+        None => (String::new(), ""),
+    };
+
+    let mut out = format!("{}{}{}{}\n\n", pad(caption.chars().count()), on, caption, off);
+    for line in lines(&time, '█', scale) {
+        out += &format!("{}{}{}{}\n", pad(width(&time, scale)), on, line.trim_end(), off);
+    }
+    out
+}
+
+/// `comodo status --big`, the clock of the timer of `client` once or redrawn
+/// as the daemon sends the status
+pub fn show(client: &Client, follow: bool) -> Result<(), String> {
+    for snapshot in client.subscribe()? {
+        let (columns, rows) = terminal::size().unwrap_or((80, 24));
+        if follow {
+            print!("\x1b[H\x1b[2J");
+        }
+        // a row is left for the prompt
+        print!("{}", clock(&snapshot, columns as usize, rows.saturating_sub(1) as usize));
+        stdout().flush().map_err(|e| e.to_string())?;
+        if !follow {
+            return Ok(());
+        }
+    }
+    Err(String::from("the daemon went away"))
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use crate::{config::RGB, session::Phase, snapshot::Snapshot};
+    #[allow(unused_imports)]
+    use super::{clock, fit, lines, width, HEIGHT};
+
+    #[test]
+    fn lines_test() {
+        let rows = lines("1:07", '#', 1);
+        assert_eq!(rows.len(), HEIGHT);
+        assert_eq!(rows[0], "    ##        ###### ######");
+        assert_eq!(rows[1], "    ##   ##   ##  ##     ##");
+        assert!(rows.iter().all(|row| row.chars().count() == width("1:07", 1)));
+        assert_eq!(lines("x", '█', 1)[2], "      ");
+
+        let rows = lines("1", '#', 2);
+        assert_eq!(rows.len(), 2 * HEIGHT);
+        assert_eq!(rows[0], "        ####");
+        assert_eq!(rows[1], rows[0]);
+    }
+
+    #[test]
+    fn fit_test() {
+        // 5 glyphs, 34 columns and 5 rows at scale 1
+        assert_eq!(width("25:00", 1), 34);
+        assert_eq!(fit("25:00", 80, 24), 2);
+        assert_eq!(fit("25:00", 200, 50), 5);
+        assert_eq!(fit("25:00", 80, 12), 2);
+        assert_eq!(fit("25:00", 10, 3), 1);
+    }
+
+    #[test]
+    fn clock_test() {
+        let snapshot = Snapshot {
+            running: true,
+            phase: Some(Phase::ShortBreak),
+            cycle: 1,
+            total: 4,
+            elapsed: 60,
+            length: 300,
+            task: Some(String::from("review")),
+            color: Some(RGB(1, 2, 3)),
+            ..Default::default()
+        };
+        let out = clock(&snapshot, 40, 7);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "           \x1b[38;2;1;2;3mrest 1/4 - review\x1b[0m");
+        assert_eq!(lines.len(), 2 + HEIGHT);
+        assert!(lines[2].starts_with("   \x1b[38;2;1;2;3m██████"));
+
+        let idle = clock(&Snapshot::idle(0), 40, 7);
+        assert!(idle.starts_with("           no session running\n\n"));
+        assert!(!idle.contains('\x1b'));
+    }
+}
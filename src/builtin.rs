@@ -0,0 +1,78 @@
+use std::{
+    fs::File, io::{self, BufReader, Cursor, Read, Seek, SeekFrom}, path::Path,
+};
+
+/// sounds compiled into the binary are addressed as `builtin:<name>`
+pub const PREFIX: &str = "builtin:";
+
+pub const SOUNDS: [(&str, &[u8]); 2] = [
+    ("bell", include_bytes!("../assets/hotel-bell-ding-1-174457.mp3")),
+    ("doorbell", include_bytes!("../assets/old-style-door-bell-101191.mp3")),
+];
+
+/// the bytes of a builtin sound, `name` can be given with or without the prefix
+pub fn get(name: &str) -> Option<&'static [u8]> {
+    let name = name.strip_prefix(PREFIX).unwrap_or(name);
+    SOUNDS.iter().find(|(n, _)| *n == name).map(|(_, bytes)| *bytes)
+}
+
+pub fn is_builtin(path: &Path) -> bool {
+    path.to_str().is_some_and(|s| s.starts_with(PREFIX))
+}
+
+/// Either a file on disk or one of the builtin sounds
+pub enum Data {
+    File(BufReader<File>),
+    Builtin(Cursor<&'static [u8]>),
+}
+
+impl Read for Data {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Data::File(file) => file.read(buf),
+            Data::Builtin(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Seek for Data {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Data::File(file) => file.seek(pos),
+            Data::Builtin(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+pub fn open(path: &Path) -> Result<Data, String> {
+    if is_builtin(path) {
+        let name = path.to_string_lossy();
+        return get(&name)
+            .map(|bytes| Data::Builtin(Cursor::new(bytes)))
+            .ok_or(format!("there is no builtin sound named {}", name));
+    }
+    File::open(path)
+        .map(|file| Data::File(BufReader::new(file)))
+        .map_err(|e| format!("could not open {}: {}", path.display(), e))
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use std::path::Path;
+    #[allow(unused_imports)]
+    use super::{get, open, SOUNDS};
+
+    #[test]
+    fn get_test() {
+        assert_eq!(get("builtin:bell").map(|b| b.len()), Some(SOUNDS[0].1.len()));
+        assert_eq!(get("doorbell").map(|b| b.len()), Some(SOUNDS[1].1.len()));
+        assert!(get("builtin:gong").is_none());
+    }
+
+    #[test]
+    fn open_test() {
+        assert!(open(Path::new("builtin:doorbell")).is_ok());
+        assert!(open(Path::new("builtin:gong")).is_err_and(|e| e.contains("no builtin sound")));
+        assert!(open(Path::new("./assets/missing.mp3")).is_err_and(|e| e.contains("could not open")));
+    }
+}
@@ -0,0 +1,239 @@
+use std::{
+    collections::BTreeMap, fs, io::ErrorKind, path::{Path, PathBuf}, time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{as_time, config::{Config, Restore}, history::{self, Tracker}, protocol::DEFAULT_TIMER, session::Session};
+
+/// seconds between two checkpoints while nothing happens, the most a restored
+/// session can be off by
+pub const HEARTBEAT: u64 = 10;
+
+/// `$XDG_STATE_HOME/comodo`, or `~/.local/state/comodo` when it is not set
+pub fn state_dir() -> Option<PathBuf> {
+    history::xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+pub fn path() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("session.json"))
+}
+
+/// What it takes to pick a running session back up after the daemon died
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// the config as streamed to the daemon
+    pub config: String,
+    pub session: Session,
+    pub history: Tracker,
+    /// when the daemon was last known to be running the session
+    pub last_seen: u64,
+}
+
+impl Checkpoint {
+    pub fn new(config: &Config, session: &Session, history: &Tracker, now: u64) -> Result<Self, String> {
+        Ok(Checkpoint {
+            config: config.to_stream_string()?,
+            session: session.clone(),
+            history: history.clone(),
+            last_seen: now,
+        })
+    }
+
+    pub fn config(&self) -> Result<Config, String> {
+        Config::from_stream_string(self.config.clone()).map_err(|e| format!("invalid config in the checkpoint: {}", e))
+    }
+
+    /// the session and its history as they were, the time the daemon was down
+    /// not counting as time spent in the phase
+    pub fn restore(self, now: u64) -> (Session, Tracker) {
+        let (mut session, mut history) = (self.session, self.history);
+        let downtime = now.saturating_sub(self.last_seen);
+        session.downtime(Duration::from_secs(downtime));
+        history.downtime(downtime);
+        (session, history)
+    }
+
+    /// a line about where the session got interrupted
+    pub fn describe(&self, now: u64) -> String {
+        let down = as_time(now.saturating_sub(self.last_seen));
+        match self.session.status(self.last_seen) {
+            Some(status) => format!("{} {}/{} with {} left, interrupted {} ago",
+                                    status.phase.name(), status.cycle, status.total,
+                                    as_time(status.length.saturating_sub(status.elapsed).as_secs()), down),
+            None => format!("a session interrupted {} ago", down),
+        }
+    }
+}
+
+/// The checkpoints of every timer running, by name, saved together
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Checkpoints(pub BTreeMap<String, Checkpoint>);
+
+impl Checkpoints {
+    /// a missing file is `None`
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map(Some)
+                .map_err(|e| format!("invalid checkpoint {}: {}", path.display(), e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("could not read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        history::replace(path, &serde_json::to_string(self).map_err(|e| e.to_string())?)
+    }
+
+    /// what the config of the default timer says to do with them, or of the
+    /// first timer without one
+    pub fn restore_mode(&self) -> Restore {
+        let checkpoint = self.0.get(DEFAULT_TIMER).or_else(|| self.0.values().next());
+        checkpoint.and_then(|c| c.config().ok()).map(|c| c.comodo.restore).unwrap_or(Restore::Never)
+    }
+
+    /// where the sessions got interrupted, the named timers by name
+    pub fn describe(&self, now: u64) -> String {
+        self.0.iter()
+            .map(|(timer, checkpoint)| match timer.as_str() {
+                DEFAULT_TIMER => checkpoint.describe(now),
+                timer => format!("{}: {}", timer, checkpoint.describe(now)),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Keeps the checkpoint file in step with the timers of the daemon
+pub struct Keeper {
+    path: Option<PathBuf>,
+    /// when the file was last written
+    last: u64,
+}
+
+impl Keeper {
+    pub fn new(path: Option<PathBuf>, now: u64) -> Self {
+        Keeper { path, last: now }
+    }
+
+    /// `changed` when a timer started, moved on, got controlled or went away
+    /// since the last call, `checkpoints` is only asked for when it gets saved
+    ///
+    /// the file is only removed by a change, a checkpoint `comodo init` left
+    /// for `comodo init --restore` outlives an idle daemon
+    pub fn update(&mut self, changed: bool, now: u64, checkpoints: impl FnOnce() -> Result<Checkpoints, String>) {
+        let Some(path) = self.path.as_ref() else { return };
+        if !changed && now.saturating_sub(self.last) < HEARTBEAT {
+            return;
+        }
+        match checkpoints() {
+            Ok(checkpoints) if checkpoints.0.is_empty() => if changed {
+                history::remove(path);
+            },
+            Ok(checkpoints) => if let Err(e) = checkpoints.save(path) {
+                eprintln!("could not save the sessions: {}", e);
+            },
+            Err(e) => eprintln!("could not save the sessions: {}", e),
+        }
+        self.last = now;
+    }
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use std::{fs, time::Duration};
+    #[allow(unused_imports)]
+    use crate::{config::Config, history::{self, Tracker}, session::{Event, Phase, Session}};
+    #[allow(unused_imports)]
+    use super::{Checkpoint, Checkpoints, Keeper, HEARTBEAT};
+
+    #[allow(dead_code)]
+    fn config() -> Config {
+        toml::de::from_str(r#"
+            [comodo]
+            iterations = 2
+            focus = "25:00"
+            rest = "05:00"
+            big_rest = "15:00"
+        "#).unwrap()
+    }
+
+    #[test]
+    fn restore_test() {
+        let config = config();
+        let mut session = Session::new(&config.comodo, 0);
+        let mut history = Tracker::new("default", None, vec![]);
+        for event in session.tick(0) {
+            history.event(event, session.timing(event, 0));
+        }
+
+        let path = std::env::temp_dir().join(format!("comodo-checkpoint-{}", std::process::id())).join("session.json");
+        assert_eq!(Checkpoints::load(&path).unwrap(), None);
+        let mut tea = Session::new(&config.comodo, 300);
+        tea.tick(300);
+        Checkpoints([
+            (String::from("default"), Checkpoint::new(&config, &session, &history, 600).unwrap()),
+            (String::from("tea"), Checkpoint::new(&config, &tea, &Tracker::new("default", None, vec![]), 600).unwrap()),
+        ].into()).save(&path).unwrap();
+
+        // the daemon died at 600 and came back at 3600
+        let mut checkpoints = Checkpoints::load(&path).unwrap().unwrap();
+        assert_eq!(checkpoints.describe(3600), "focus 1/2 with 15:00 left, interrupted 50:00 ago, tea: focus 1/2 with 20:00 left, interrupted 50:00 ago");
+        assert_eq!(checkpoints.0.remove("tea").unwrap().restore(3600).0.status(3600).unwrap().elapsed, Duration::from_secs(300));
+        let checkpoint = checkpoints.0.remove("default").unwrap();
+        assert_eq!(checkpoint.config().unwrap().comodo.focus, Duration::from_secs(1500));
+        let (mut session, mut history) = checkpoint.restore(3600);
+        assert_eq!(session.status(3600).unwrap().elapsed, Duration::from_secs(600));
+        assert_eq!(session.tick(3600), vec![]);
+
+        let event = session.tick(3600 + 900);
+        assert_eq!(event, vec![Event::PhaseStarted { phase: Phase::ShortBreak, cycle: 1 }]);
+        let record = history.event(event[0], session.timing(event[0], 4500)).unwrap();
+        assert_eq!((record.actual, record.paused), (1500, 3000));
+
+        history::remove(&path);
+        assert_eq!(Checkpoints::load(&path).unwrap(), None);
+        fs::remove_dir(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn paused_restore_test() {
+        let config = config();
+        let mut session = Session::new(&config.comodo, 0);
+        session.tick(0);
+        session.pause(100);
+        let checkpoint = Checkpoint::new(&config, &session, &Tracker::new("default", None, vec![]), 200).unwrap();
+
+        // paused when it died, the downtime is part of the pause already
+        let (mut session, _) = checkpoint.restore(1000);
+        assert_eq!(session.status(1000).unwrap().elapsed, Duration::from_secs(100));
+        session.resume(1000);
+        assert_eq!(session.status(1100).unwrap().elapsed, Duration::from_secs(200));
+    }
+
+    #[test]
+    fn keeper_test() {
+        let path = std::env::temp_dir().join(format!("comodo-keeper-{}", std::process::id())).join("session.json");
+        let config = config();
+        let session = Session::new(&config.comodo, 0);
+        let running = || Ok(Checkpoints([
+            (String::from("default"), Checkpoint::new(&config, &session, &Tracker::new("default", None, vec![]), 0)?),
+        ].into()));
+        running().unwrap().save(&path).unwrap();
+
+        // left by `comodo init` for `--restore`, an idle daemon does not touch it
+        let mut keeper = Keeper::new(Some(path.clone()), 100);
+        keeper.update(false, 100, || Ok(Checkpoints::default()));
+        keeper.update(false, 100 + 10 * HEARTBEAT, || Ok(Checkpoints::default()));
+        assert!(Checkpoints::load(&path).unwrap().is_some());
+
+        // a session that went away takes it with it
+        keeper.update(true, 200, running);
+        assert_eq!(Checkpoints::load(&path).unwrap().unwrap().0.len(), 1);
+        keeper.update(true, 201, || Ok(Checkpoints::default()));
+        assert_eq!(Checkpoints::load(&path).unwrap(), None);
+        fs::remove_dir(path.parent().unwrap()).unwrap();
+    }
+}
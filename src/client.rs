@@ -0,0 +1,259 @@
+use std::{
+    fmt,
+    io::{BufRead, BufReader, Lines, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    time::Duration,
+};
+
+use crate::{
+    config::Config,
+    protocol::{send_start, to_timer, DEFAULT_TIMER, EXTEND, PAUSE, RESUME, SKIP, SOCKET_PATH, STATE_PATH, STATUS_PATH, STOP},
+    snapshot::Snapshot,
+};
+
+/// Why a session did not start
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Refused {
+    /// the daemon would only pick it up once the running session is over
+    Running,
+    Unreachable(String),
+}
+
+impl fmt::Display for Refused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Refused::Running => write!(f, "a session is running already"),
+            Refused::Unreachable(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Controls a timer of a running daemon through its sockets
+#[derive(Debug, Clone)]
+pub struct Client {
+    pub socket_path: PathBuf,
+    pub state_path: PathBuf,
+    pub status_path: PathBuf,
+    /// the timer the commands are for
+    pub timer: String,
+}
+
+fn unreachable(e: std::io::Error) -> String {
+    format!("could not reach the daemon, is `comodo init` running? {}", e)
+}
+
+impl Client {
+    /// a client of the default timer of the daemon on `socket_path`,
+    /// `state_path` and `status_path`, nothing is checked until it is used
+    pub fn new(socket_path: impl Into<PathBuf>, state_path: impl Into<PathBuf>, status_path: impl Into<PathBuf>) -> Self {
+        Client {
+            socket_path: socket_path.into(),
+            state_path: state_path.into(),
+            status_path: status_path.into(),
+            timer: String::from(DEFAULT_TIMER),
+        }
+    }
+
+    /// the same daemon, the timer `name` rather than this one
+    pub fn timer(self, name: impl Into<String>) -> Self {
+        Client { timer: name.into(), ..self }
+    }
+
+    /// a client of the daemon `comodo init` runs, an error when there is none
+    pub fn connect() -> Result<Self, String> {
+        let client = Client::new(SOCKET_PATH, STATE_PATH, STATUS_PATH);
+        UnixStream::connect(&client.status_path).map_err(unreachable)?;
+        Ok(client)
+    }
+
+    /// writes `command` for the timer to the state socket, see [`crate::protocol`]
+    pub fn send(&self, command: &[u8]) -> Result<(), String> {
+        UnixStream::connect(&self.state_path).map_err(unreachable)
+            .and_then(|mut stream| stream.write_all(&to_timer(&self.timer, command)).map_err(|e| e.to_string()))
+    }
+
+    /// a session of `config` starting now, refused while the timer runs another one
+    pub fn start(&self, config: &Config) -> Result<(), Refused> {
+        match self.status() {
+            Ok(snapshot) if snapshot.running => return Err(Refused::Running),
+            Ok(_) => {},
+            Err(e) => return Err(Refused::Unreachable(e)),
+        }
+        send_start(self.socket_path.to_str().unwrap_or_default(), &self.timer, config)
+            .map_err(|e| Refused::Unreachable(unreachable(e)))
+    }
+
+    pub fn pause(&self) -> Result<(), String> {
+        self.send(&PAUSE)
+    }
+
+    pub fn resume(&self) -> Result<(), String> {
+        self.send(&RESUME)
+    }
+
+    pub fn stop(&self) -> Result<(), String> {
+        self.send(&STOP)
+    }
+
+    pub fn skip(&self) -> Result<(), String> {
+        self.send(&SKIP)
+    }
+
+    /// the current phase gets `by` longer, to the second
+    pub fn extend(&self, by: Duration) -> Result<(), String> {
+        self.send(&[&EXTEND[..], &by.as_secs().to_be_bytes()].concat())
+    }
+
+    /// the next snapshot the daemon sends, within a tick
+    pub fn status(&self) -> Result<Snapshot, String> {
+        self.subscribe()?.next().ok_or(String::from("the daemon went away"))
+    }
+
+    /// every snapshot of the timer from now on, a tick apart
+    pub fn subscribe(&self) -> Result<Subscription, String> {
+        let stream = UnixStream::connect(&self.status_path).map_err(unreachable)?;
+        Ok(Subscription { lines: BufReader::new(stream).lines(), timer: self.timer.clone(), seen: false })
+    }
+
+    /// the snapshots of every timer at the next tick, the default one first
+    /// whether it runs or not
+    pub fn timers(&self) -> Result<Vec<Snapshot>, String> {
+        let stream = UnixStream::connect(&self.status_path).map_err(unreachable)?;
+        let mut lines = BufReader::new(stream).lines();
+        let mut timers = vec![];
+        while let Some(snapshot) = next_snapshot(&mut lines) {
+            if snapshot.timer() == DEFAULT_TIMER {
+                timers.insert(0, snapshot);
+                return Ok(timers);
+            }
+            timers.push(snapshot);
+        }
+        Err(String::from("the daemon went away"))
+    }
+}
+
+// what can not be made sense of is skipped, a newer daemon may send more
+fn next_snapshot(lines: &mut Lines<BufReader<UnixStream>>) -> Option<Snapshot> {
+    lines.map_while(Result::ok).find_map(|line| serde_json::from_str(&line).ok())
+}
+
+/// The snapshots of [`Client::subscribe`], it ends when the daemon goes away
+pub struct Subscription {
+    lines: Lines<BufReader<UnixStream>>,
+    timer: String,
+    /// whether the timer was in the tick going on
+    seen: bool,
+}
+
+impl Iterator for Subscription {
+    type Item = Snapshot;
+
+    fn next(&mut self) -> Option<Snapshot> {
+        loop {
+            let snapshot = next_snapshot(&mut self.lines)?;
+            // the default timer closes every tick
+            let closing = snapshot.timer() == DEFAULT_TIMER;
+            if snapshot.timer() == self.timer {
+                self.seen = !closing;
+                return Some(snapshot);
+            }
+            // a timer the daemon does not run is idle
+            if closing && !std::mem::replace(&mut self.seen, false) {
+                return Some(Snapshot { timer: Some(self.timer.clone()), ..Snapshot::idle(snapshot.today) });
+            }
+        }
+    }
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use std::{
+        fs, io::{Read, Write}, os::unix::net::UnixListener, thread, time::Duration,
+    };
+    #[allow(unused_imports)]
+    use crate::{protocol::{to_timer, DEFAULT, EXTEND, PAUSE, STOP}, session::Phase, snapshot::Snapshot};
+    #[allow(unused_imports)]
+    use super::{Client, Refused};
+
+    #[test]
+    fn client_test() {
+        let dir = std::env::temp_dir().join(format!("comodo-client-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let client = Client::new(dir.join("comodo.sock"), dir.join("state.sock"), dir.join("status.sock"));
+        assert!(client.pause().unwrap_err().contains("could not reach the daemon"));
+
+        let socket = UnixListener::bind(&client.socket_path).unwrap();
+        let state = UnixListener::bind(&client.state_path).unwrap();
+        let status = UnixListener::bind(&client.status_path).unwrap();
+        let daemon = thread::spawn(move || {
+            for running in [false, true, true] {
+                let (mut stream, _) = status.accept().unwrap();
+                let snapshot = Snapshot { running, phase: Some(Phase::Focus), cycle: 1, total: 4, elapsed: 10, length: 1500, ..Default::default() };
+                stream.write_all(b"not a snapshot\n").unwrap();
+                stream.write_all(format!("{}\n", serde_json::to_string(&snapshot).unwrap()).as_bytes()).unwrap();
+            }
+            let mut commands = vec![];
+            for _ in 0..2 {
+                let mut command = vec![];
+                state.accept().unwrap().0.read_to_end(&mut command).unwrap();
+                commands.push(command);
+            }
+            let mut start = vec![];
+            socket.accept().unwrap().0.read_to_end(&mut start).unwrap();
+            (commands, start)
+        });
+
+        // idle, the session is handed over
+        let config = crate::cli_config(4, Duration::from_secs(1500), Duration::from_secs(300));
+        client.start(&config).unwrap();
+        let snapshot = client.subscribe().unwrap().next().unwrap();
+        assert_eq!(snapshot.remaining(), 1490);
+        assert_eq!(client.start(&config), Err(Refused::Running));
+        client.pause().unwrap();
+        client.extend(Duration::from_secs(300)).unwrap();
+
+        let (commands, start) = daemon.join().unwrap();
+        assert_eq!(commands[0], PAUSE);
+        assert_eq!(commands[1], [&EXTEND[..], &300u64.to_be_bytes()].concat());
+        assert_eq!(start[..1], DEFAULT);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn timers_test() {
+        let dir = std::env::temp_dir().join(format!("comodo-timers-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let client = Client::new(dir.join("comodo.sock"), dir.join("state.sock"), dir.join("status.sock"));
+        let state = UnixListener::bind(&client.state_path).unwrap();
+        let status = UnixListener::bind(&client.status_path).unwrap();
+        let daemon = thread::spawn(move || {
+            // a tick with the tea timer running and the default one idle, then one without
+            let tea = Snapshot { timer: Some(String::from("tea")), running: true, phase: Some(Phase::Focus), cycle: 1, total: 1, length: 240, ..Default::default() };
+            let idle = Snapshot { today: 2, ..Default::default() };
+            for _ in 0..3 {
+                let (mut stream, _) = status.accept().unwrap();
+                // the client hangs up once it has what it asked for, the rest goes nowhere
+                for snapshot in [&tea, &idle, &idle] {
+                    stream.write_all(format!("{}\n", serde_json::to_string(snapshot).unwrap()).as_bytes()).ok();
+                }
+            }
+            let mut command = vec![];
+            state.accept().unwrap().0.read_to_end(&mut command).unwrap();
+            command
+        });
+
+        let timers = client.timers().unwrap();
+        assert_eq!(timers.iter().map(|s| (s.timer(), s.running)).collect::<Vec<_>>(), [("default", false), ("tea", true)]);
+        let mut tea = client.clone().timer("tea").subscribe().unwrap();
+        assert_eq!(tea.next().unwrap().remaining(), 240);
+        // gone from the next tick, it is idle
+        let gone = tea.next().unwrap();
+        assert_eq!((gone.timer(), gone.running, gone.today), ("tea", false, 2));
+        assert!(!client.clone().timer("coffee").status().unwrap().running);
+
+        client.timer("tea").stop().unwrap();
+        assert_eq!(daemon.join().unwrap(), to_timer("tea", &STOP));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,10 +1,33 @@
 use std::{fs, time::Duration, path::{Path, PathBuf}};
 // use serde::{Serialize, Deserialize, de::{self, Visitor}};
-use serde::{ser::{SerializeStruct, Serializer}, Deserialize, Serialize, Deserializer,  de::Error};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Deserializer,  de::Error};
 
-#[derive(Debug, Clone, Copy)]
+use crate::{ambient::Color, builtin, session::{Event, Phase}, template::Template};
+
+/// A color written `#rrggbb`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
 pub struct RGB(pub u8, pub u8, pub u8);
 
+impl TryFrom<String> for RGB {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let hex = value.strip_prefix('#').unwrap_or(&value);
+        let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+        match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(RGB(r, g, b)),
+            _ => Err(format!("invalid color {:?}, expected #rrggbb", value)),
+        }
+    }
+}
+
+impl From<RGB> for String {
+    fn from(rgb: RGB) -> Self {
+        format!("#{:02x}{:02x}{:02x}", rgb.0, rgb.1, rgb.2)
+    }
+}
+
 //                        Deserialization
 // Toml Representation -------------------> Config Structure Object
 //                        Serialization
@@ -12,47 +35,65 @@ pub struct RGB(pub u8, pub u8, pub u8);
 
 struct ComodoDefaults;
 impl ComodoDefaults{
-    pub fn focus()->Duration{ Duration::from_secs(1500) }
-    pub fn rest()->Duration{ Duration::from_secs(300) }
-    pub fn big_rest()->Duration{ Duration::from_secs(900) }
-    pub fn iterations()->u8{4}
+    pub fn long_break_interval()->u8{4}
     pub fn popup_notification() -> bool { true }
     pub fn sound_notification() -> bool { false }
     pub fn focus_notification_banner() -> String { String::from("Focus Time!") }
     pub fn rest_notification_banner() -> String { String::from("Resting Time!") }
+    pub fn end_notification_banner() -> String { String::from("End of Session!") }
+    pub fn notification_summary() -> String { String::from("Comodo Pomodoro") }
     pub fn focus_notification_path() -> Option<Box<PathBuf>> { None }
     pub fn rest_notification_path() -> Option<Box<PathBuf>> { None }
 }
 
-fn from_str_to_duration(s: &str) -> Result<Duration, std::num::ParseIntError> {
+fn from_str_to_duration(s: &str) -> Result<Duration, String> {
     let parts: Vec<&str> = s.split(':').collect();
-    let minutes = parts[0].parse::<u64>()?;
-    let seconds = parts[1].parse::<u64>()?;
+    if parts.len() != 2 {
+        return Err(String::from("expected mm:ss"));
+    }
+    let minutes = parts[0].parse::<u64>().map_err(|e| e.to_string())?;
+    let seconds = parts[1].parse::<u64>().map_err(|e| e.to_string())?;
     Ok(Duration::from_secs(minutes * 60 + seconds))
 }
 
+// builtin sounds always exist, an unknown one is an error while a missing file is dropped
+fn sound_path<E: Error>(value: &str) -> Result<Option<Box<PathBuf>>, E> {
+    let path = Path::new(value).to_owned();
+    if builtin::is_builtin(&path) {
+        return match builtin::get(value) {
+            Some(_) => Ok(Some(Box::new(path))),
+            None => Err(E::custom(format!("there is no builtin sound named {}", value))),
+        };
+    }
+    if path.exists() {
+        Ok(Some(Box::new(path)))
+    } else {
+        Ok(None)
+    }
+}
+
 // TODO: handle this more properly, mainly 'config does not exist!' error situation
 fn deserialize_path<'de, D>(d: D) -> Result<Option<Box<PathBuf>>, D::Error>
     where D: Deserializer<'de>
 {
     let s: Option<String> = Option::deserialize(d)?;
     match s {
-        Some(value) => {
-            let path = Path::new(value.as_str()).to_owned();
-            if path.exists() {
-                return Ok(Some(Box::new(path)))
-            }else {
-                return Ok(None)
-            };
-        },
-        None => return Ok(None),
+        Some(value) => sound_path(&value),
+        None => Ok(None),
     }
 }
 
-// Substitute the # withthe thing
+// the banner is kept as written, it is only parsed here so that a bad
+// placeholder is reported when the config is loaded instead of at notification time
 fn deserialize_banner<'de, D>(d: D) -> Result<String, D::Error>
     where D: Deserializer<'de>
-{todo!()}
+{
+    let s = String::deserialize(d)?;
+    match Template::new(&s) {
+        Ok(_) => Ok(s),
+        Err(e) => Err(D::Error::custom(format!("invalid template {:?}: {}", s, e))),
+    }
+}
 
 fn deserialize_time<'de, D>(d: D) -> Result<Duration, D::Error>
     where D: Deserializer<'de>
@@ -60,7 +101,8 @@ fn deserialize_time<'de, D>(d: D) -> Result<Duration, D::Error>
     let s: Option<String> = Option::deserialize(d)?;
     match s {
         Some(value) => {
-            Ok(from_str_to_duration(&value).unwrap())
+            from_str_to_duration(&value)
+                .map_err(|e| D::Error::custom(format!("invalid time {:?}: {}", value, e)))
         },
         None => Ok(Duration::from_secs(300)),
     }
@@ -81,15 +123,222 @@ pub struct Comodo{
     #[serde(default = "ComodoDefaults::sound_notification")]
     pub sound_notification: bool,
 
-    #[serde(default = "ComodoDefaults::focus_notification_banner")]
+    #[serde(deserialize_with = "deserialize_banner", default = "ComodoDefaults::focus_notification_banner")]
     pub focus_notification_banner: String,
-    #[serde(default = "ComodoDefaults::rest_notification_banner")]
+    #[serde(deserialize_with = "deserialize_banner", default = "ComodoDefaults::rest_notification_banner")]
     pub rest_notification_banner: String,
+    #[serde(deserialize_with = "deserialize_banner", default = "ComodoDefaults::end_notification_banner")]
+    pub end_notification_banner: String,
+    #[serde(deserialize_with = "deserialize_banner", default = "ComodoDefaults::notification_summary")]
+    pub notification_summary: String,
 
     #[serde(deserialize_with = "deserialize_path", default = "ComodoDefaults::focus_notification_path")]
     pub focus_audio_notification_path: Option<Box<PathBuf>>,
     #[serde(deserialize_with = "deserialize_path", default = "ComodoDefaults::rest_notification_path")]
     pub rest_audio_notification_path: Option<Box<PathBuf>>,
+
+    /// every how many cycles the rest is replaced by a `big_rest`, 0 disables it
+    #[serde(default = "ComodoDefaults::long_break_interval")]
+    pub long_break_interval: u8,
+
+    /// name or part of the name of the output device, see `comodo sounds devices`
+    #[serde(default)]
+    pub audio_device: Option<String>,
+
+    /// what the sessions are recorded as in the history, the config file name when not set
+    #[serde(default)]
+    pub profile: Option<String>,
+
+    /// what `comodo init` does with a session the daemon died in the middle of
+    #[serde(default)]
+    pub restore: Restore,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Restore {
+    /// asks on the terminal `comodo init` runs in
+    #[default]
+    Ask,
+    Auto,
+    Never,
+}
+
+struct SoundDefaults;
+impl SoundDefaults {
+    pub fn volume() -> u16 { 100 }
+    pub fn repeat() -> u32 { 1 }
+}
+
+/// A sound of the `[sounds]` table, written either as a bare path or as a
+/// table with a `path` and the playback settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sound {
+    pub path: Box<PathBuf>,
+    /// in percent, from 0 to 200
+    pub volume: u16,
+    pub fade_in: Duration,
+    pub fade_out: Duration,
+    /// how many times the file is played in a row
+    pub repeat: u32,
+    /// a longer file is cut (and faded out) once it plays for this long
+    pub max_duration: Option<Duration>,
+    /// keep playing until `comodo ack` or the next transition
+    pub until_ack: bool,
+}
+
+impl Sound {
+    pub fn new(path: Box<PathBuf>) -> Self {
+        Sound {
+            path,
+            volume: SoundDefaults::volume(),
+            fade_in: Duration::ZERO,
+            fade_out: Duration::ZERO,
+            repeat: SoundDefaults::repeat(),
+            max_duration: None,
+            until_ack: false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SoundEntry {
+    Path(String),
+    Table(SoundTable),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SoundTable {
+    path: String,
+    #[serde(default = "SoundDefaults::volume")]
+    volume: u16,
+    /// in seconds
+    #[serde(default)]
+    fade_in: f32,
+    #[serde(default)]
+    fade_out: f32,
+    #[serde(default = "SoundDefaults::repeat")]
+    repeat: u32,
+    #[serde(default)]
+    max_duration: Option<String>,
+    #[serde(default)]
+    until_ack: bool,
+}
+
+// same as `deserialize_path`, a sound whose file does not exist is dropped
+fn deserialize_sound<'de, D>(d: D) -> Result<Option<Sound>, D::Error>
+    where D: Deserializer<'de>
+{
+    let entry: Option<SoundEntry> = Option::deserialize(d)?;
+    let table = match entry {
+        Some(SoundEntry::Path(path)) => SoundTable {
+            path,
+            volume: SoundDefaults::volume(),
+            fade_in: 0.,
+            fade_out: 0.,
+            repeat: SoundDefaults::repeat(),
+            max_duration: None,
+            until_ack: false,
+        },
+        Some(SoundEntry::Table(table)) => table,
+        None => return Ok(None),
+    };
+
+    if table.volume > 200 {
+        return Err(D::Error::custom(format!("volume of {} is {}%, it can go from 0 to 200", table.path, table.volume)));
+    }
+    if !table.fade_in.is_finite() || !table.fade_out.is_finite() {
+        return Err(D::Error::custom(format!("fades of {} must be a number of seconds", table.path)));
+    }
+    if table.fade_in < 0. || table.fade_out < 0. {
+        return Err(D::Error::custom(format!("fades of {} can't be negative", table.path)));
+    }
+    let max_duration = match table.max_duration {
+        Some(value) => Some(from_str_to_duration(&value)
+            .map_err(|e| D::Error::custom(format!("invalid max_duration {:?}: {}", value, e)))?),
+        None => None,
+    };
+
+    let Some(path) = sound_path(&table.path)? else {
+        return Ok(None);
+    };
+
+    Ok(Some(Sound {
+        path,
+        volume: table.volume,
+        fade_in: Duration::from_secs_f32(table.fade_in),
+        fade_out: Duration::from_secs_f32(table.fade_out),
+        repeat: table.repeat,
+        max_duration,
+        until_ack: table.until_ack,
+    }))
+}
+
+impl Serialize for Sound {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer {
+            let mut s = serializer.serialize_struct("Sound", 7)?;
+            s.serialize_field("path", self.path.as_ref())?;
+            s.serialize_field("volume", &self.volume)?;
+            s.serialize_field("fade_in", &self.fade_in.as_secs_f32())?;
+            s.serialize_field("fade_out", &self.fade_out.as_secs_f32())?;
+            s.serialize_field("repeat", &self.repeat)?;
+            if let Some(max_duration) = self.max_duration {
+                s.serialize_field("max_duration", &from_duration_to_str(max_duration))?;
+            }
+            s.serialize_field("until_ack", &self.until_ack)?;
+            s.end()
+        }
+}
+
+/// The `[sounds]` table, one optional sound per transition
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct Sounds {
+    #[serde(deserialize_with = "deserialize_sound", default, skip_serializing_if = "Option::is_none")]
+    pub focus_start: Option<Sound>,
+    #[serde(deserialize_with = "deserialize_sound", default, skip_serializing_if = "Option::is_none")]
+    pub short_break_start: Option<Sound>,
+    #[serde(deserialize_with = "deserialize_sound", default, skip_serializing_if = "Option::is_none")]
+    pub long_break_start: Option<Sound>,
+    #[serde(deserialize_with = "deserialize_sound", default, skip_serializing_if = "Option::is_none")]
+    pub session_complete: Option<Sound>,
+    #[serde(deserialize_with = "deserialize_sound", default, skip_serializing_if = "Option::is_none")]
+    pub pause: Option<Sound>,
+    #[serde(deserialize_with = "deserialize_sound", default, skip_serializing_if = "Option::is_none")]
+    pub resume: Option<Sound>,
+}
+
+impl Sounds {
+    /// fill the missing entries from the older `*_audio_notification_path` keys,
+    /// the focus one is used when focusing starts and the rest one for both breaks
+    pub fn with_legacy(&self, comodo: &Comodo) -> Sounds {
+        let mut sounds = self.clone();
+        if sounds.focus_start.is_none() {
+            sounds.focus_start = comodo.focus_audio_notification_path.clone().map(Sound::new);
+        }
+        if sounds.short_break_start.is_none() {
+            sounds.short_break_start = comodo.rest_audio_notification_path.clone().map(Sound::new);
+        }
+        if sounds.long_break_start.is_none() {
+            sounds.long_break_start = comodo.rest_audio_notification_path.clone().map(Sound::new);
+        }
+        sounds
+    }
+
+    pub fn for_event(&self, event: Event) -> Option<&Sound> {
+        let sound = match event {
+            Event::PhaseStarted { phase: Phase::Focus, .. } => &self.focus_start,
+            Event::PhaseStarted { phase: Phase::ShortBreak, .. } => &self.short_break_start,
+            Event::PhaseStarted { phase: Phase::LongBreak, .. } => &self.long_break_start,
+            Event::SessionComplete => &self.session_complete,
+            Event::Paused => &self.pause,
+            Event::Resumed => &self.resume,
+        };
+        sound.as_ref()
+    }
 }
 
 pub fn from_duration_to_str(duration: Duration) -> String{
@@ -114,51 +363,450 @@ impl Serialize for Comodo {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer {
-            let mut s = serializer.serialize_struct("Comodo", 4)?;
+            let mut s = serializer.serialize_struct("Comodo", 16)?;
             s.serialize_field("iterations", &self.iterations)?;
             s.serialize_field("focus", &from_duration_to_str(self.focus))?;
             s.serialize_field("rest", &from_duration_to_str(self.rest))?;
             s.serialize_field("big_rest", &from_duration_to_str(self.big_rest))?;
+            s.serialize_field("popup_notification", &self.popup_notification)?;
+            s.serialize_field("sound_notification", &self.sound_notification)?;
+            s.serialize_field("focus_notification_banner", &self.focus_notification_banner)?;
+            s.serialize_field("rest_notification_banner", &self.rest_notification_banner)?;
+            s.serialize_field("end_notification_banner", &self.end_notification_banner)?;
+            s.serialize_field("notification_summary", &self.notification_summary)?;
+            s.serialize_field("long_break_interval", &self.long_break_interval)?;
+            if let Some(audio_device) = &self.audio_device {
+                s.serialize_field("audio_device", audio_device)?;
+            }
+            if let Some(profile) = &self.profile {
+                s.serialize_field("profile", profile)?;
+            }
+            s.serialize_field("restore", &self.restore)?;
+            if let Some(path) = &self.focus_audio_notification_path {
+                s.serialize_field("focus_audio_notification_path", path.as_ref())?;
+            }
+            if let Some(path) = &self.rest_audio_notification_path {
+                s.serialize_field("rest_audio_notification_path", path.as_ref())?;
+            }
             s.end()
         }
 }
 
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackgroundSource {
+    /// looped for as long as the phase lasts
+    File(Box<PathBuf>),
+    Tick,
+    Noise(Color),
+}
+
+impl BackgroundSource {
+    fn name(&self) -> String {
+        match self {
+            BackgroundSource::File(path) => path.to_string_lossy().to_string(),
+            BackgroundSource::Tick => String::from("tick"),
+            BackgroundSource::Noise(Color::White) => String::from("white"),
+            BackgroundSource::Noise(Color::Pink) => String::from("pink"),
+            BackgroundSource::Noise(Color::Brown) => String::from("brown"),
+        }
+    }
+}
+
+/// A track playing in the background of a phase
+#[derive(Debug, Clone, PartialEq)]
+pub struct Background {
+    pub source: BackgroundSource,
+    /// in percent, from 0 to 200
+    pub volume: u16,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BackgroundEntry {
+    Source(String),
+    Table {
+        source: String,
+        #[serde(default = "SoundDefaults::volume")]
+        volume: u16,
+    },
+}
+
+// `tick`, `white`, `pink` and `brown` are generated, anything else is a path
+fn deserialize_background<'de, D>(d: D) -> Result<Option<Background>, D::Error>
+    where D: Deserializer<'de>
+{
+    let entry: Option<BackgroundEntry> = Option::deserialize(d)?;
+    let (source, volume) = match entry {
+        Some(BackgroundEntry::Source(source)) => (source, SoundDefaults::volume()),
+        Some(BackgroundEntry::Table { source, volume }) => (source, volume),
+        None => return Ok(None),
+    };
+
+    if volume > 200 {
+        return Err(D::Error::custom(format!("volume of {} is {}%, it can go from 0 to 200", source, volume)));
+    }
+
+    let source = match source.as_str() {
+        "tick" => BackgroundSource::Tick,
+        "white" => BackgroundSource::Noise(Color::White),
+        "pink" => BackgroundSource::Noise(Color::Pink),
+        "brown" => BackgroundSource::Noise(Color::Brown),
+        path => match sound_path(path)? {
+            Some(path) => BackgroundSource::File(path),
+            None => return Ok(None),
+        },
+    };
+
+    Ok(Some(Background { source, volume }))
+}
+
+impl Serialize for Background {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer {
+            let mut s = serializer.serialize_struct("Background", 2)?;
+            s.serialize_field("source", &self.source.name())?;
+            s.serialize_field("volume", &self.volume)?;
+            s.end()
+        }
+}
+
+struct AmbientDefaults;
+impl AmbientDefaults {
+    pub fn ducking() -> u16 { 25 }
+}
+
+/// The `[ambient]` table, one optional background track per phase
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Ambient {
+    #[serde(deserialize_with = "deserialize_background", default, skip_serializing_if = "Option::is_none")]
+    pub focus: Option<Background>,
+    #[serde(deserialize_with = "deserialize_background", default, skip_serializing_if = "Option::is_none")]
+    pub short_break: Option<Background>,
+    #[serde(deserialize_with = "deserialize_background", default, skip_serializing_if = "Option::is_none")]
+    pub long_break: Option<Background>,
+    /// percent of its volume the track keeps while a notification sound plays
+    #[serde(default = "AmbientDefaults::ducking")]
+    pub ducking: u16,
+}
+
+impl Default for Ambient {
+    fn default() -> Self {
+        Ambient {
+            focus: None,
+            short_break: None,
+            long_break: None,
+            ducking: AmbientDefaults::ducking(),
+        }
+    }
+}
+
+impl Ambient {
+    pub fn for_phase(&self, phase: Phase) -> Option<&Background> {
+        match phase {
+            Phase::Focus => self.focus.as_ref(),
+            Phase::ShortBreak => self.short_break.as_ref(),
+            Phase::LongBreak => self.long_break.as_ref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Desktop,
+    Bell,
+    File,
+    Clients,
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Urgency {
+    Low,
+    Normal,
+    Critical,
+}
+
+struct PopupDefaults;
+impl PopupDefaults {
+    pub fn urgency() -> Urgency { Urgency::Normal }
+    pub fn timeout() -> u32 { 2 }
+    pub fn actions() -> bool { true }
+}
+
+/// How the desktop popups of a phase look and behave
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Popup {
+    #[serde(default = "PopupDefaults::urgency")]
+    pub urgency: Urgency,
+    /// a freedesktop icon name or a path to an image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    /// in seconds, 0 keeps it until dismissed
+    #[serde(default = "PopupDefaults::timeout")]
+    pub timeout: u32,
+    /// whether to offer the pause/skip/+5 min buttons
+    #[serde(default = "PopupDefaults::actions")]
+    pub actions: bool,
+}
+
+impl Default for Popup {
+    fn default() -> Self {
+        Popup {
+            urgency: PopupDefaults::urgency(),
+            icon: None,
+            timeout: PopupDefaults::timeout(),
+            actions: PopupDefaults::actions(),
+        }
+    }
+}
+
+struct NotificationsDefaults;
+impl NotificationsDefaults {
+    pub fn backends() -> Vec<Backend> { vec![Backend::Desktop] }
+}
+
+#[derive(Deserialize)]
+struct NotificationsTable {
+    #[serde(default = "NotificationsDefaults::backends")]
+    backends: Vec<Backend>,
+    #[serde(default)]
+    file: Option<PathBuf>,
+    #[serde(default)]
+    focus: Popup,
+    #[serde(default)]
+    short_break: Popup,
+    #[serde(default)]
+    long_break: Popup,
+    #[serde(default)]
+    end: Popup,
+    #[serde(default)]
+    countdown: u32,
+}
+
+/// The `[notifications]` table, every backend listed gets the notifications
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(try_from = "NotificationsTable")]
+pub struct Notifications {
+    pub backends: Vec<Backend>,
+    /// written to by the `file` backend
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    pub focus: Popup,
+    pub short_break: Popup,
+    pub long_break: Popup,
+    /// the end of session popup
+    pub end: Popup,
+    /// seconds between the updates of the live countdown popup, 0 turns it off
+    pub countdown: u32,
+}
+
+impl TryFrom<NotificationsTable> for Notifications {
+    type Error = String;
+
+    fn try_from(table: NotificationsTable) -> Result<Self, Self::Error> {
+        if table.backends.contains(&Backend::File) && table.file.is_none() {
+            return Err(String::from("the file notification backend needs a `file` to write to"));
+        }
+        Ok(Notifications {
+            backends: table.backends,
+            file: table.file,
+            focus: table.focus,
+            short_break: table.short_break,
+            long_break: table.long_break,
+            end: table.end,
+            countdown: table.countdown,
+        })
+    }
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Notifications {
+            backends: NotificationsDefaults::backends(),
+            file: None,
+            focus: Popup::default(),
+            short_break: Popup::default(),
+            long_break: Popup::default(),
+            end: Popup::default(),
+            countdown: 0,
+        }
+    }
+}
+
+impl Notifications {
+    /// `None` is the end of the session
+    pub fn popup(&self, phase: Option<Phase>) -> &Popup {
+        match phase {
+            Some(Phase::Focus) => &self.focus,
+            Some(Phase::ShortBreak) => &self.short_break,
+            Some(Phase::LongBreak) => &self.long_break,
+            None => &self.end,
+        }
+    }
+}
+
+struct HooksDefaults;
+impl HooksDefaults {
+    pub fn timeout() -> u32 { 10 }
+}
+
+/// The `[hooks]` table, shell commands run by the daemon as the session goes
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Hooks {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_focus_start: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_break_start: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_long_break_start: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_pause: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_resume: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_session_end: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_stop: Option<String>,
+    /// in seconds, a hook still running after it is killed
+    #[serde(default = "HooksDefaults::timeout")]
+    pub timeout: u32,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Hooks {
+            on_focus_start: None,
+            on_break_start: None,
+            on_long_break_start: None,
+            on_pause: None,
+            on_resume: None,
+            on_session_end: None,
+            on_stop: None,
+            timeout: HooksDefaults::timeout(),
+        }
+    }
+}
+
+struct ColorsDefaults;
+impl ColorsDefaults {
+    pub fn focus() -> RGB { RGB(0xe0, 0x6c, 0x75) }
+    pub fn short_break() -> RGB { RGB(0x98, 0xc3, 0x79) }
+    pub fn long_break() -> RGB { RGB(0x61, 0xaf, 0xef) }
+    pub fn paused() -> RGB { RGB(0xe5, 0xc0, 0x7b) }
+}
+
+/// The `[colors]` table, what the big clock and the tui paint each phase with
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct Colors {
+    #[serde(default = "ColorsDefaults::focus")]
+    pub focus: RGB,
+    #[serde(default = "ColorsDefaults::short_break")]
+    pub short_break: RGB,
+    #[serde(default = "ColorsDefaults::long_break")]
+    pub long_break: RGB,
+    /// whatever the phase
+    #[serde(default = "ColorsDefaults::paused")]
+    pub paused: RGB,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Colors {
+            focus: ColorsDefaults::focus(),
+            short_break: ColorsDefaults::short_break(),
+            long_break: ColorsDefaults::long_break(),
+            paused: ColorsDefaults::paused(),
+        }
+    }
+}
+
+impl Colors {
+    pub fn for_phase(&self, phase: Phase, paused: bool) -> RGB {
+        match phase {
+            _ if paused => self.paused,
+            Phase::Focus => self.focus,
+            Phase::ShortBreak => self.short_break,
+            Phase::LongBreak => self.long_break,
+        }
+    }
+}
+
+/// The `[http]` table, read by `comodo init`, the daemon serves no api without it
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct Http {
+    /// a loopback `address:port`, or the path of a unix socket
+    pub listen: String,
+    /// every request has to bring it as `Authorization: Bearer <token>` or `?token=<token>`,
+    /// tcp is not served without one, a unix socket without one only by its owner
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// the web pages allowed to call the api from a browser, as `http://localhost:3000`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub origins: Vec<String>,
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Config {
+    /// what the session is spent on, `comodo start --task` overrides it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
     #[serde(rename="comodo")]
     pub comodo: Comodo,
+    #[serde(default)]
+    pub sounds: Sounds,
+    #[serde(default)]
+    pub ambient: Ambient,
+    #[serde(default)]
+    pub notifications: Notifications,
+    #[serde(default)]
+    pub hooks: Hooks,
+    #[serde(default)]
+    pub colors: Colors,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http: Option<Http>,
 }
 
 impl Config {
 
     /// this will
     /// 1) deserialize the file 
-    pub fn read(path: String) -> Config{
-        // reading the env variable for the config path
-
+    /// 2) validate the banners templates
+    pub fn read(path: String) -> Result<Config, String> {
         // stands for string config
-        let sconfig = fs::read_to_string(path).unwrap();
+        let sconfig = fs::read_to_string(&path)
+            .map_err(|e| format!("could not read {}: {}", path, e))?;
 
-        let comodo: Comodo = toml::de::from_str(sconfig.as_str()).unwrap();
+        toml::de::from_str(sconfig.as_str())
+            .map_err(|e| format!("could not parse {}: {}", path, e))
+    }
 
-        Config {
-            comodo
-        }
+    /// the daemon receives the config the same way it is written on disk
+    pub fn from_stream_string(input: String) -> Result<Self, toml::de::Error> {
+        toml::de::from_str(input.as_str())
     }
 
-    pub fn from_stream_string(input: String) -> Self {todo!()}
+    pub fn to_stream_string(&self) -> Result<String, String> {
+        toml::ser::to_string(self).map_err(|e| format!("could not serialize the config: {}", e))
+    }
 
 }
 
 mod test{
-    use std::fs;
+    #[allow(unused_imports)]
+    use std::{fs, path::{Path, PathBuf}, time::Duration};
+    #[allow(unused_imports)]
+    use crate::{ambient::Color, session::{Event, Phase}};
+    #[allow(unused_imports)]
+    use super::{Background, BackgroundSource, Backend, Colors, Hooks, Popup, Restore, Urgency, RGB};
 
     #[allow(unused_imports)]
     use crate::config::Config;
-    use toml;
-
-    use super::Comodo;
 
     #[test]
     fn contains_test() {
@@ -180,4 +828,282 @@ mod test{
         let comodo: Config = toml::de::from_str(content.as_str()).unwrap();
         println!("comodo: {:#?}", comodo);
     }
+
+    #[test]
+    fn banner_validation_test() {
+        let conf: Result<Config, _> = toml::de::from_str(r#"
+            [comodo]
+            iterations = 4
+            focus = "25:00"
+            rest = "05:00"
+            big_rest = "15:00"
+            focus_notification_banner = "cycle {cycle} of {totl}"
+        "#);
+        assert!(conf.unwrap_err().to_string().contains("unknown placeholder `{totl}`"));
+
+        let conf: Result<Config, _> = toml::de::from_str(r#"
+            [comodo]
+            iterations = 4
+            focus = "25:xx"
+            rest = "05:00"
+            big_rest = "15:00"
+        "#);
+        assert!(conf.unwrap_err().to_string().contains("invalid time \"25:xx\""));
+    }
+
+    #[test]
+    fn stream_string_test() {
+        let content = fs::read_to_string("./comodo.toml").unwrap();
+        let config: Config = toml::de::from_str(content.as_str()).unwrap();
+        let streamed = Config::from_stream_string(config.to_stream_string().unwrap()).unwrap();
+        assert_eq!(streamed.comodo.focus, config.comodo.focus);
+        assert_eq!(streamed.comodo.focus_notification_banner, config.comodo.focus_notification_banner);
+        assert_eq!(streamed.comodo.notification_summary, config.comodo.notification_summary);
+        assert_eq!(streamed.sounds.session_complete, config.sounds.session_complete);
+    }
+
+    #[test]
+    fn sounds_test() {
+        let conf: Config = toml::de::from_str(r#"
+            [comodo]
+            iterations = 4
+            focus = "25:00"
+            rest = "05:00"
+            big_rest = "15:00"
+            focus_audio_notification_path = "./assets/hotel-bell-ding-1-174457.mp3"
+
+            [sounds]
+            session_complete = "./assets/old-style-door-bell-101191.mp3"
+            pause = "./assets/does-not-exist.mp3"
+            resume = "builtin:bell"
+        "#).unwrap();
+        let sounds = conf.sounds.with_legacy(&conf.comodo);
+        assert_eq!(sounds.for_event(Event::PhaseStarted { phase: Phase::Focus, cycle: 1 }).map(|s| s.path.as_path()),
+            Some(Path::new("./assets/hotel-bell-ding-1-174457.mp3")));
+        assert_eq!(sounds.for_event(Event::SessionComplete).map(|s| s.path.as_path()),
+            Some(Path::new("./assets/old-style-door-bell-101191.mp3")));
+        assert_eq!(sounds.for_event(Event::PhaseStarted { phase: Phase::ShortBreak, cycle: 1 }), None);
+        assert_eq!(sounds.for_event(Event::Paused), None);
+        assert_eq!(sounds.for_event(Event::Resumed).map(|s| s.path.as_path()), Some(Path::new("builtin:bell")));
+
+        let conf: Result<Config, _> = toml::de::from_str(r#"
+            [comodo]
+            iterations = 4
+            focus = "25:00"
+            rest = "05:00"
+            big_rest = "15:00"
+
+            [sounds]
+            resume = "builtin:gong"
+        "#);
+        assert!(conf.unwrap_err().to_string().contains("no builtin sound named builtin:gong"));
+    }
+
+    #[test]
+    fn sound_settings_test() {
+        let conf: Config = toml::de::from_str(r#"
+            [comodo]
+            iterations = 4
+            focus = "25:00"
+            rest = "05:00"
+            big_rest = "15:00"
+
+            [sounds]
+            focus_start = { path = "./assets/old-style-door-bell-101191.mp3", volume = 150, fade_out = 0.5, max_duration = "00:03", until_ack = true }
+        "#).unwrap();
+        let sound = conf.sounds.focus_start.clone().unwrap();
+        assert_eq!(sound.volume, 150);
+        assert_eq!(sound.fade_in, Duration::ZERO);
+        assert_eq!(sound.fade_out, Duration::from_millis(500));
+        assert_eq!(sound.repeat, 1);
+        assert_eq!(sound.max_duration, Some(Duration::from_secs(3)));
+        assert!(sound.until_ack);
+
+        let streamed = Config::from_stream_string(conf.to_stream_string().unwrap()).unwrap();
+        assert_eq!(streamed.sounds.focus_start, conf.sounds.focus_start);
+
+        let conf: Result<Config, _> = toml::de::from_str(r#"
+            [comodo]
+            iterations = 4
+            focus = "25:00"
+            rest = "05:00"
+            big_rest = "15:00"
+
+            [sounds]
+            pause = { path = "./assets/old-style-door-bell-101191.mp3", volume = 250 }
+        "#);
+        assert!(conf.unwrap_err().to_string().contains("it can go from 0 to 200"));
+
+        for (sound, error) in [
+            (r#"max_duration = "5""#, "invalid max_duration \"5\": expected mm:ss"),
+            ("fade_in = inf", "must be a number of seconds"),
+            ("fade_out = nan", "must be a number of seconds"),
+        ] {
+            let conf: Result<Config, _> = toml::de::from_str(&format!(r#"
+                [comodo]
+                iterations = 4
+                focus = "25:00"
+                rest = "05:00"
+                big_rest = "15:00"
+
+                [sounds]
+                pause = {{ path = "./assets/old-style-door-bell-101191.mp3", {} }}
+            "#, sound));
+            assert!(conf.unwrap_err().to_string().contains(error), "{}", sound);
+        }
+    }
+
+    #[test]
+    fn ambient_test() {
+        let conf: Config = toml::de::from_str(r#"
+            [comodo]
+            iterations = 4
+            focus = "25:00"
+            rest = "05:00"
+            big_rest = "15:00"
+
+            [ambient]
+            focus = { source = "brown", volume = 40 }
+            short_break = "./assets/hotel-bell-ding-1-174457.mp3"
+            long_break = "./assets/missing.mp3"
+        "#).unwrap();
+        assert_eq!(conf.ambient.for_phase(Phase::Focus),
+            Some(&Background { source: BackgroundSource::Noise(Color::Brown), volume: 40 }));
+        assert_eq!(conf.ambient.for_phase(Phase::ShortBreak).map(|b| b.volume), Some(100));
+        assert_eq!(conf.ambient.for_phase(Phase::LongBreak), None);
+        assert_eq!(conf.ambient.ducking, 25);
+
+        let streamed = Config::from_stream_string(conf.to_stream_string().unwrap()).unwrap();
+        assert_eq!(streamed.ambient.focus, conf.ambient.focus);
+        assert_eq!(streamed.ambient.short_break, conf.ambient.short_break);
+    }
+
+    #[test]
+    fn notifications_test() {
+        let conf: Config = toml::de::from_str(r#"
+            [comodo]
+            iterations = 4
+            focus = "25:00"
+            rest = "05:00"
+            big_rest = "15:00"
+        "#).unwrap();
+        assert_eq!(conf.notifications.backends, vec![Backend::Desktop]);
+
+        let conf: Config = toml::de::from_str(r#"
+            [comodo]
+            iterations = 4
+            focus = "25:00"
+            rest = "05:00"
+            big_rest = "15:00"
+
+            [notifications]
+            backends = ["bell", "file", "clients"]
+            file = "/tmp/comodo.fifo"
+
+            countdown = 5
+
+            [notifications.short_break]
+            urgency = "critical"
+            icon = "alarm-clock"
+            timeout = 0
+            actions = false
+        "#).unwrap();
+        let popup = conf.notifications.popup(Some(Phase::ShortBreak));
+        assert_eq!(popup.urgency, Urgency::Critical);
+        assert_eq!(popup.icon.as_deref(), Some("alarm-clock"));
+        assert_eq!(popup.timeout, 0);
+        assert!(!popup.actions);
+        assert_eq!(conf.notifications.popup(None), &Popup::default());
+        assert_eq!(conf.notifications.countdown, 5);
+        let streamed = Config::from_stream_string(conf.to_stream_string().unwrap()).unwrap();
+        assert_eq!(streamed.notifications.backends, vec![Backend::Bell, Backend::File, Backend::Clients]);
+        assert_eq!(streamed.notifications.popup(Some(Phase::LongBreak)), &Popup::default());
+        assert_eq!(streamed.notifications.file, Some(PathBuf::from("/tmp/comodo.fifo")));
+
+        let conf: Result<Config, _> = toml::de::from_str(r#"
+            [comodo]
+            iterations = 4
+            focus = "25:00"
+            rest = "05:00"
+            big_rest = "15:00"
+
+            [notifications]
+            backends = ["file"]
+        "#);
+        assert!(conf.unwrap_err().to_string().contains("needs a `file`"));
+    }
+
+    #[test]
+    fn hooks_test() {
+        let conf: Config = toml::de::from_str(r#"
+            [comodo]
+            iterations = 1
+            focus = "25:00"
+            rest = "05:00"
+            big_rest = "15:00"
+
+            [hooks]
+            on_focus_start = "dunstctl set-paused true"
+            on_break_start = "dunstctl set-paused false"
+            timeout = 3
+        "#).unwrap();
+        assert_eq!(conf.hooks.on_focus_start.as_deref(), Some("dunstctl set-paused true"));
+        assert_eq!(conf.hooks.on_stop, None);
+        assert_eq!(conf.hooks.timeout, 3);
+        assert_eq!(conf.comodo.restore, Restore::Ask);
+
+        let mut conf = conf;
+        conf.task = Some(String::from("review PR 42"));
+        conf.tags = vec![String::from("review")];
+        let streamed = Config::from_stream_string(conf.to_stream_string().unwrap()).unwrap();
+        assert_eq!(streamed.hooks, conf.hooks);
+        assert_eq!((streamed.task, streamed.tags), (conf.task, conf.tags));
+        let conf: Config = toml::de::from_str("[comodo]\niterations = 1\nfocus = \"25:00\"\nrest = \"05:00\"\nbig_rest = \"15:00\"").unwrap();
+        assert_eq!(conf.hooks, Hooks::default());
+    }
+
+    #[test]
+    fn colors_test() {
+        let conf: Config = toml::de::from_str(r##"
+            [comodo]
+            iterations = 1
+            focus = "25:00"
+            rest = "05:00"
+            big_rest = "15:00"
+
+            [colors]
+            focus = "#ff0000"
+            paused = "808080"
+        "##).unwrap();
+        assert_eq!(conf.colors.focus, RGB(255, 0, 0));
+        assert_eq!(conf.colors.short_break, Colors::default().short_break);
+        assert_eq!(conf.colors.for_phase(Phase::Focus, true), RGB(128, 128, 128));
+        assert_eq!(conf.colors.for_phase(Phase::LongBreak, false), Colors::default().long_break);
+        assert_eq!(Config::from_stream_string(conf.to_stream_string().unwrap()).unwrap().colors, conf.colors);
+
+        let conf = toml::de::from_str::<Config>("[comodo]\niterations = 1\nfocus = \"25:00\"\nrest = \"05:00\"\nbig_rest = \"15:00\"\n[colors]\nfocus = \"#ff00\"");
+        assert!(conf.unwrap_err().to_string().contains("invalid color"));
+    }
+
+    #[test]
+    fn http_test() {
+        let conf: Config = toml::de::from_str(r#"
+            [comodo]
+            iterations = 1
+            focus = "25:00"
+            rest = "05:00"
+            big_rest = "15:00"
+
+            [http]
+            listen = "127.0.0.1:7878"
+            token = "secret"
+            origins = ["http://localhost:3000"]
+        "#).unwrap();
+        let http = conf.http.clone().unwrap();
+        assert_eq!((http.listen.as_str(), http.token.as_deref()), ("127.0.0.1:7878", Some("secret")));
+        assert_eq!(http.origins, vec![String::from("http://localhost:3000")]);
+        assert_eq!(Config::from_stream_string(conf.to_stream_string().unwrap()).unwrap().http, conf.http);
+        let conf: Config = toml::de::from_str("[comodo]\niterations = 1\nfocus = \"25:00\"\nrest = \"05:00\"\nbig_rest = \"15:00\"").unwrap();
+        assert_eq!(conf.http, None);
+    }
 }
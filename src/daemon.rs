@@ -0,0 +1,456 @@
+use std::{
+    collections::{BTreeMap, HashMap}, fs::File, io::Read, os::unix::net::{UnixListener, UnixStream},
+    rc::Rc, thread, time::Duration,
+};
+
+use chrono::{Local, NaiveDate};
+use sysinfo::System;
+
+use crate::{
+    now_in_secs,
+    checkpoint::{self, Checkpoint, Checkpoints, Keeper},
+    api::{self, Api},
+    config::{Comodo, Config, Http, Sounds},
+    dbus,
+    history::{self, Tracker},
+    hooks::{self, Hook},
+    notifier::{self, Action, Countdown, Message, Notifier},
+    player::AudioService,
+    prompt::{self, Segment, StatusFile},
+    protocol::{read_timer, ACK, DEFAULT, DEFAULT_TIMER, EXTEND, INTERRUPT, INTERRUPT_EXTERNAL, INTERRUPT_PAUSE, KILL, PAUSE, RESUME, SKIP, STOP, TASK},
+    session::{Event, Phase, Session},
+    snapshot::Snapshot,
+    template::{Context, Template},
+};
+
+/// the values the notification banners can refer to
+fn banner_context(cycle: u64, total: u64, phase_length: Duration, next_phase: &str, task: &str, today_count: u64) -> Context {
+    Context {
+        cycle,
+        total,
+        phase_length,
+        next_phase: next_phase.to_string(),
+        task: task.to_string(),
+        today_count,
+        time: Local::now().format("%H:%M").to_string(),
+    }
+}
+
+struct Banners {
+    focus: Template,
+    rest: Template,
+    end: Template,
+    summary: Template,
+}
+
+impl Banners {
+    // validated while deserializing already, a config built in code is not
+    fn new(comodo: &Comodo) -> Result<Self, String> {
+        let parse = |source: &str| Template::new(source).map_err(|e| format!("invalid template {:?}: {}", source, e));
+        Ok(Banners {
+            focus: parse(&comodo.focus_notification_banner)?,
+            rest: parse(&comodo.rest_notification_banner)?,
+            end: parse(&comodo.end_notification_banner)?,
+            summary: parse(&comodo.notification_summary)?,
+        })
+    }
+}
+
+// a phase crossed while the ticks were apart reports itself, not the one running at `now`
+fn hook_env(session: &Session, event: Option<Event>, timer: &str, task: &str, now: u64) -> hooks::Env {
+    if let Some(event @ Event::PhaseStarted { phase, cycle }) = event {
+        let timing = session.timing(event, now);
+        return hooks::Env {
+            timer: timer.to_string(),
+            phase: Some(phase),
+            cycle,
+            total: session.total(),
+            task: task.to_string(),
+            remaining: (timing.at + timing.planned.as_secs()).saturating_sub(now),
+        };
+    }
+    match session.status(now) {
+        Some(status) => hooks::Env {
+            timer: timer.to_string(),
+            phase: Some(status.phase),
+            cycle: status.cycle,
+            total: status.total,
+            task: task.to_string(),
+            remaining: status.length.saturating_sub(status.elapsed).as_secs(),
+        },
+        None => hooks::Env {
+            timer: timer.to_string(),
+            cycle: session.total(),
+            total: session.total(),
+            task: task.to_string(),
+            ..Default::default()
+        },
+    }
+}
+
+// the count is reset by the first pomodoro of the day, until then yesterday's is stale
+fn today_count(today: (NaiveDate, u64)) -> u64 {
+    if today.0 == Local::now().date_naive() { today.1 } else { 0 }
+}
+
+/// A session the daemon is running along with what it needs to notify about it
+struct Running {
+    config: Config,
+    sounds: Sounds,
+    banners: Banners,
+    notifiers: Vec<Box<dyn Notifier>>,
+    countdown: Option<Countdown>,
+    history: Tracker,
+    session: Session,
+}
+
+impl Running {
+    fn new(timer: &str, config: Config, session: Session, history: Tracker, context: &notifier::Context) -> Result<Self, String> {
+        Ok(Running {
+            sounds: config.sounds.with_legacy(&config.comodo),
+            banners: Banners::new(&config.comodo)?,
+            notifiers: if config.comodo.popup_notification {
+                notifier::build(&config.notifications, context, timer)
+            } else {
+                vec![]
+            },
+            countdown: if config.comodo.popup_notification {
+                Countdown::new(&config.notifications, config.task.clone())
+            } else {
+                None
+            },
+            history,
+            session,
+            config,
+        })
+    }
+
+    /// a session of `config` started at `started`
+    fn start(timer: &str, config: Config, started: u64, context: &notifier::Context) -> Result<Self, String> {
+        let session = Session::new(&config.comodo, started);
+        let history = Tracker::new(config.comodo.profile.as_deref().unwrap_or("default"), config.task.clone(), config.tags.clone());
+        Running::new(timer, config, session, history, context)
+    }
+}
+
+impl Drop for Running {
+    // however the session ends, the live countdown goes with it
+    fn drop(&mut self) {
+        if let Some(countdown) = self.countdown.as_mut() {
+            countdown.close();
+        }
+    }
+}
+
+enum Request {
+    Start { timer: String, started: u64, config: Box<Config> },
+    Kill,
+}
+
+fn read_request(stream: &mut UnixStream) -> Result<Request, String> {
+    let (timer, kind) = read_timer(stream).map_err(|e| format!("received an invalid request: {}", e))?;
+    if kind == KILL {
+        return Ok(Request::Kill);
+    }
+
+    let mut started = [0; 8];
+    stream.read_exact(&mut started).unwrap_or(());
+
+    let mut sequence = String::new();
+    stream.read_to_string(&mut sequence).unwrap_or(0);
+    let config = Config::from_stream_string(sequence)
+        .map_err(|e| format!("received an invalid config: {}", e))?;
+    Ok(Request::Start { timer, started: u64::from_be_bytes(started), config: Box::new(config) })
+}
+
+/// The daemon main loop, it never returns unless it is killed
+///
+/// `tty` is the terminal the daemon was started from, opened before
+/// daemonizing since it is no longer reachable afterward, and `restore`
+/// the session a previous daemon died in the middle of, `http` the api to
+/// serve if any
+pub fn run(socket_path: &str, state_path: &str, watch_path: &str, status_path: &str, tty: Option<File>, restore: Option<Checkpoints>, http: Option<Http>) {
+    let audio = AudioService::spawn();
+    let hooks = hooks::Runner::spawn();
+    let context = notifier::Context {
+        tty: tty.map(Rc::new),
+        state_path: state_path.into(),
+        ..Default::default()
+    };
+
+    // focus phases completed by this daemon on the given day
+    let mut today = (Local::now().date_naive(), 0);
+    let socket_stream = UnixListener::bind(socket_path).unwrap();
+    let state_stream = UnixListener::bind(state_path).unwrap();
+
+    let watch_stream = UnixListener::bind(watch_path).unwrap();
+    let status_stream = UnixListener::bind(status_path).unwrap();
+    // the clients following the status, they get a snapshot every tick
+    let subscribers = notifier::Clients::default();
+    let mut status_file = StatusFile::new(prompt::path());
+    // gateways to the sockets above, they have to be bound first
+    if let Some(http) = http {
+        if let Err(e) = api::spawn(&http, Api::new(socket_path, state_path, status_path, http.token.clone())) {
+            eprintln!("{}", e);
+        }
+    }
+    if let Err(e) = dbus::spawn(Api::new(socket_path, state_path, status_path, None)) {
+        eprintln!("{}", e);
+    }
+
+    socket_stream.set_nonblocking(true).unwrap();
+    state_stream.set_nonblocking(true).unwrap();
+    watch_stream.set_nonblocking(true).unwrap();
+    status_stream.set_nonblocking(true).unwrap();
+
+    // the sessions running, by timer
+    let mut timers: BTreeMap<String, Box<Running>> = BTreeMap::new();
+    // the sessions asked for timers still running one, in the order they came
+    let mut waiting: Vec<(String, u64, Box<Config>)> = vec![];
+    let mut keeper = Keeper::new(checkpoint::path(), now_in_secs());
+    // the timer the background track plays for, there is one track for all of them
+    let mut ambient_timer: Option<String> = None;
+
+    let now = now_in_secs();
+    for (timer, checkpoint) in restore.unwrap_or_default().0 {
+        println!("restoring {}: {}", timer, checkpoint.describe(now));
+        let restored = checkpoint.config().and_then(|config| {
+            let (session, history) = checkpoint.restore(now);
+            Running::new(&timer, config, session, history, &context)
+        });
+        match restored {
+            Ok(running) => {
+                // its background track is picked up by the first tick
+                audio.device(running.config.comodo.audio_device.as_deref());
+                timers.insert(timer, Box::new(running));
+            },
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    loop {
+        thread::sleep(Duration::from_millis(500));
+        let now = now_in_secs();
+        let mut events: HashMap<String, Vec<Event>> = HashMap::new();
+        // whether a session got controlled or moved on since the last checkpoint
+        let mut changed = false;
+
+        while let Ok((stream, _)) = watch_stream.accept() {
+            context.clients.attach(stream);
+        }
+        while let Ok((stream, _)) = status_stream.accept() {
+            subscribers.attach(stream);
+        }
+
+        while let Ok((mut stream, _)) = socket_stream.accept() {
+            match read_request(&mut stream) {
+                Ok(Request::Kill) => {
+                    let s = System::new_all();
+                    for process in s.processes_by_name("comodo") {
+                        process.kill();
+                    }
+                    return;
+                },
+                Ok(Request::Start { timer, started, config }) => waiting.push((timer, started, config)),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        // a new session of a timer is only picked up once its current one is over
+        for (timer, started, config) in std::mem::take(&mut waiting) {
+            if timers.contains_key(&timer) {
+                waiting.push((timer, started, config));
+                continue;
+            }
+            // starting over counts as moving on from the last alarm
+            audio.acknowledge();
+            audio.device(config.comodo.audio_device.as_deref());
+            match Running::start(&timer, *config, started, &context) {
+                Ok(running) => {
+                    timers.insert(timer, Box::new(running));
+                },
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+
+        // checking for signals
+        while let Ok((mut stream, _)) = state_stream.accept() {
+            let (timer, action) = read_timer(&mut stream).unwrap_or((String::from(DEFAULT_TIMER), DEFAULT));
+
+            if action == ACK {
+                audio.acknowledge();
+            }
+            let Some(Running { config, countdown, history, session, .. }) = timers.get_mut(&timer).map(|r| r.as_mut()) else {
+                continue;
+            };
+            let events = events.entry(timer.clone()).or_default();
+            changed = true;
+            if action == PAUSE {
+                println!("pause recived");
+                events.extend(session.pause(now));
+            }else if action == RESUME {
+                events.extend(session.resume(now));
+            }else if action == SKIP {
+                history.skip();
+                session.skip(now);
+            }else if action == EXTEND {
+                let mut seconds = [0; 8];
+                if stream.read_exact(&mut seconds).is_ok() {
+                    session.extend(now, Duration::from_secs(u64::from_be_bytes(seconds)));
+                }
+            }else if action == INTERRUPT {
+                let mut flags = [0];
+                stream.read_exact(&mut flags).unwrap_or(());
+                let mut note = String::new();
+                stream.read_to_string(&mut note).unwrap_or(0);
+                let note = Some(note.trim().to_string()).filter(|n| !n.is_empty());
+                if !history.interrupt(now, flags[0] & INTERRUPT_EXTERNAL != 0, note) {
+                    eprintln!("interruption ignored, not focusing");
+                }
+                if flags[0] & INTERRUPT_PAUSE != 0 {
+                    events.extend(session.pause(now));
+                }
+            }else if action == TASK {
+                let mut lines = String::new();
+                stream.read_to_string(&mut lines).unwrap_or(0);
+                let mut lines = lines.lines().map(String::from).filter(|l| !l.is_empty());
+                config.task = lines.next();
+                config.tags = lines.collect();
+                history.set_task(config.task.clone(), config.tags.clone());
+                if let Some(countdown) = countdown.as_mut() {
+                    countdown.set_task(config.task.clone());
+                }
+            }else if action == STOP {
+                hooks.run(&config.hooks, Hook::Stop, &hook_env(session, None, &timer, config.task.as_deref().unwrap_or_default(), now));
+                history::record(history.stop(now));
+                if ambient_timer.as_ref() == Some(&timer) {
+                    audio.background(None, 0);
+                    ambient_timer = None;
+                }
+                timers.remove(&timer);
+            }
+        }
+
+        // the default timer closes the tick, idle or not
+        let mut default = None;
+        for (timer, running) in timers.iter_mut() {
+            let Running { config, sounds, banners, notifiers, countdown, history, session } = running.as_mut();
+            let comodo = &config.comodo;
+            let task = config.task.as_deref().unwrap_or_default();
+
+            let next_phase = session.next_phase(now).map(|p| p.name()).unwrap_or("end");
+            let mut events = events.remove(timer).unwrap_or_default();
+            events.extend(session.tick(now));
+            changed |= !events.is_empty();
+
+            for event in events {
+                let banner = match event {
+                    Event::PhaseStarted { phase: Phase::Focus, cycle } => {
+                        Some((&banners.focus, Some(Phase::Focus),
+                              banner_context(cycle, session.total(), comodo.focus, next_phase, task, today.1)))
+                    },
+                    Event::PhaseStarted { phase, cycle } => {
+                        // a focus phase just got completed, a skipped one does not count
+                        if !history.skipped() {
+                            let date = Local::now().date_naive();
+                            today = if today.0 == date { (date, today.1 + 1) } else { (date, 1) };
+                        }
+                        let length = if phase == Phase::LongBreak { comodo.big_rest } else { comodo.rest };
+                        Some((&banners.rest, Some(phase),
+                              banner_context(cycle, session.total(), length, next_phase, task, today.1)))
+                    },
+                    Event::SessionComplete => {
+                        Some((&banners.end, None,
+                              banner_context(session.total(), session.total(), Duration::ZERO, "end", task, today.1)))
+                    },
+                    Event::Paused | Event::Resumed => None,
+                };
+
+                hooks.run(&config.hooks, Hook::for_event(event), &hook_env(session, Some(event), timer, task, now));
+                history::record(history.event(event, session.timing(event, now)));
+
+                // the other timers' track is only taken over by one of this timer's own
+                let owned = ambient_timer.as_ref() == Some(timer);
+                match event {
+                    Event::PhaseStarted { phase, .. } => {
+                        audio.acknowledge();
+                        let track = config.ambient.for_phase(phase);
+                        if track.is_some() || owned {
+                            audio.background(track, config.ambient.ducking);
+                            ambient_timer = track.map(|_| timer.clone());
+                        }
+                    },
+                    Event::SessionComplete => {
+                        audio.acknowledge();
+                        if owned {
+                            audio.background(None, 0);
+                            ambient_timer = None;
+                        }
+                    },
+                    Event::Paused if owned => audio.pause_background(),
+                    Event::Resumed if owned => audio.resume_background(),
+                    Event::Paused | Event::Resumed => (),
+                }
+
+                if let Some((banner, phase, context)) = banner {
+                    let summary = banners.summary.render(&context);
+                    let message = Message {
+                        // which of the timers it is about, the default one goes without
+                        summary: if timer == DEFAULT_TIMER { summary } else { format!("{}: {}", timer, summary) },
+                        body: banner.render(&context),
+                        phase,
+                        actions: phase.map(Action::for_phase).unwrap_or_default(),
+                    };
+                    notifier::notify_all(notifiers, &message);
+                }
+                if comodo.sound_notification {
+                    if let Some(sound) = sounds.for_event(event) {
+                        audio.play(sound);
+                    }
+                }
+            }
+
+            let snapshot = Snapshot {
+                timer: Some(timer.clone()),
+                ..Snapshot::new(session, config.task.as_deref(), &config.tags, history.interruptions(), today_count(today), &config.colors, now)
+            };
+            if timer == DEFAULT_TIMER {
+                default = Some(snapshot);
+            } else {
+                subscribers.broadcast(&serde_json::to_string(&snapshot).unwrap());
+            }
+
+            if let (Some(countdown), Some(status)) = (countdown.as_mut(), session.status(now)) {
+                if let Err(e) = countdown.update(&status, now) {
+                    eprintln!("could not update the countdown: {}", e);
+                }
+            }
+        }
+
+        let default = default.unwrap_or_else(|| Snapshot::idle(today_count(today)));
+        subscribers.broadcast(&serde_json::to_string(&default).unwrap());
+        status_file.update(Segment::new(&default, now));
+
+        timers.retain(|_, running| !running.session.is_complete());
+
+        // every timer still running, by name, nothing left to restore once they are all over
+        keeper.update(changed, now, || {
+            timers.iter()
+                .map(|(timer, running)| Checkpoint::new(&running.config, &running.session, &running.history, now).map(|c| (timer.clone(), c)))
+                .collect::<Result<_, String>>()
+                .map(Checkpoints)
+        });
+
+        // a track let go of goes to another timer with one for its current phase
+        if ambient_timer.is_none() {
+            let next = timers.iter().find_map(|(timer, running)| {
+                let status = running.session.status(now).filter(|s| !s.paused)?;
+                let ambient = &running.config.ambient;
+                ambient.for_phase(status.phase).map(|track| (timer, track, ambient.ducking))
+            });
+            if let Some((timer, track, ducking)) = next {
+                audio.background(Some(track), ducking);
+                ambient_timer = Some(timer.clone());
+            }
+        }
+    }
+}
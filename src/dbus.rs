@@ -0,0 +1,234 @@
+use std::thread;
+
+use zbus::{
+    blocking::{Connection, ConnectionBuilder, InterfaceRef},
+    dbus_interface, fdo, SignalContext,
+};
+
+use crate::{
+    api::{Api, Start},
+    client::Client,
+    snapshot::Snapshot,
+};
+
+pub const NAME: &str = "org.comodo.Timer";
+pub const PATH: &str = "/org/comodo/Timer";
+
+/// The timer on the bus, a gateway to the sockets of the daemon like the
+/// http api, the properties are those of the last status the default timer sent
+///
+/// the methods take the name of the timer first, empty for the default one
+pub struct Timer {
+    api: Api,
+    snapshot: Snapshot,
+}
+
+fn failed(e: impl ToString) -> fdo::Error {
+    fdo::Error::Failed(e.to_string())
+}
+
+// `focus`, `short_break` or `long_break`, empty while no session runs
+fn phase_id(snapshot: &Snapshot) -> &'static str {
+    snapshot.phase.filter(|_| snapshot.running).map(|p| p.id()).unwrap_or_default()
+}
+
+#[dbus_interface(name = "org.comodo.Timer")]
+impl Timer {
+    /// durations as `25m`, `90s` or `25:00`, empty strings and 0 cycles stand
+    /// for the defaults of `comodo run`
+    fn start(&self, timer: &str, focus: &str, rest: &str, cycles: u8, task: &str) -> fdo::Result<()> {
+        let given = |s: &str| Some(s.to_string()).filter(|s| !s.is_empty());
+        let start = Start {
+            focus: given(focus),
+            rest: given(rest),
+            number: Some(cycles).filter(|c| *c > 0),
+            task: given(task),
+            ..Default::default()
+        };
+        let config = start.config().map_err(fdo::Error::InvalidArgs)?;
+        self.api.for_timer(Some(timer)).start(&config).map_err(failed)
+    }
+
+    fn pause(&self, timer: &str) -> fdo::Result<()> {
+        self.api.for_timer(Some(timer)).pause().map_err(failed)
+    }
+
+    fn resume(&self, timer: &str) -> fdo::Result<()> {
+        self.api.for_timer(Some(timer)).resume().map_err(failed)
+    }
+
+    fn stop(&self, timer: &str) -> fdo::Result<()> {
+        self.api.for_timer(Some(timer)).stop().map_err(failed)
+    }
+
+    fn skip(&self, timer: &str) -> fdo::Result<()> {
+        self.api.for_timer(Some(timer)).skip().map_err(failed)
+    }
+
+    #[dbus_interface(property)]
+    fn phase(&self) -> String {
+        phase_id(&self.snapshot).to_string()
+    }
+
+    /// seconds left in the phase
+    #[dbus_interface(property)]
+    fn remaining(&self) -> u64 {
+        self.snapshot.remaining()
+    }
+
+    #[dbus_interface(property)]
+    fn cycle(&self) -> u64 {
+        self.snapshot.cycle
+    }
+
+    /// on the start of every phase, and with an empty phase once the session is over
+    #[dbus_interface(signal, name = "PhaseChanged")]
+    async fn phase_started(ctxt: &SignalContext<'_>, phase: &str, cycle: u64) -> zbus::Result<()>;
+
+    /// every second the timer runs
+    #[dbus_interface(signal)]
+    async fn tick(ctxt: &SignalContext<'_>, remaining: u64) -> zbus::Result<()>;
+}
+
+fn update(timer: &InterfaceRef<Timer>, snapshot: Snapshot) -> zbus::Result<()> {
+    let ctxt = timer.signal_context();
+    let previous = std::mem::replace(&mut timer.get_mut().snapshot, snapshot.clone());
+    let iface = timer.get();
+    zbus::block_on(async {
+        if (phase_id(&previous), previous.cycle) != (phase_id(&snapshot), snapshot.cycle) {
+            iface.phase_changed(ctxt).await?;
+            iface.cycle_changed(ctxt).await?;
+            Timer::phase_started(ctxt, phase_id(&snapshot), snapshot.cycle).await?;
+        }
+        if snapshot.running && previous.remaining() != snapshot.remaining() {
+            iface.remaining_changed(ctxt).await?;
+            Timer::tick(ctxt, snapshot.remaining()).await?;
+        }
+        Ok(())
+    })
+}
+
+// the connection goes along, the timer stays on the bus for as long as it runs
+fn follow(connection: Connection, client: &Client) {
+    let timer = match connection.object_server().interface::<_, Timer>(PATH) {
+        Ok(timer) => timer,
+        Err(e) => return eprintln!("the timer is not on the bus: {}", e),
+    };
+    let snapshots = match client.subscribe() {
+        Ok(snapshots) => snapshots,
+        Err(e) => return eprintln!("could not follow the status for the bus: {}", e),
+    };
+    for snapshot in snapshots {
+        if let Err(e) = update(&timer, snapshot) {
+            eprintln!("could not signal on the bus: {}", e);
+        }
+    }
+}
+
+/// Puts the timer on the bus of `builder` under [`NAME`], it is kept up to
+/// date from a thread
+pub fn serve(builder: ConnectionBuilder<'static>, api: Api) -> Result<(), String> {
+    let client = api.client.clone();
+    let connection = builder
+        .name(NAME)
+        .and_then(|builder| builder.serve_at(PATH, Timer { api, snapshot: Snapshot::default() }))
+        .and_then(|builder| builder.build())
+        .map_err(|e| format!("could not put {} on the bus: {}", NAME, e))?;
+    thread::spawn(move || follow(connection, &client));
+    Ok(())
+}
+
+/// [`serve`] on the session bus, when there is one
+pub fn spawn(api: Api) -> Result<(), String> {
+    let builder = ConnectionBuilder::session().map_err(|e| format!("no session bus: {}", e))?;
+    serve(builder, api)
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use std::{
+        fs, io::{BufRead, BufReader, Read, Write}, os::unix::net::UnixListener,
+        process::{Command, Stdio}, sync::mpsc, thread, time::Duration,
+    };
+    #[allow(unused_imports)]
+    use zbus::{blocking::{ConnectionBuilder, ProxyBuilder}, CacheProperties};
+    #[allow(unused_imports)]
+    use crate::{api::Api, client::Client, protocol::{to_timer, PAUSE}, session::Phase, snapshot::Snapshot};
+    #[allow(unused_imports)]
+    use super::{serve, NAME, PATH};
+
+    #[test]
+    fn timer_test() {
+        // a private bus, skipped where there is no dbus-daemon to run one
+        let Ok(mut bus) = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .spawn() else {
+            eprintln!("no dbus-daemon, skipped");
+            return;
+        };
+        let mut address = String::new();
+        BufReader::new(bus.stdout.take().unwrap()).read_line(&mut address).unwrap();
+        let address = address.trim().to_string();
+
+        let dir = std::env::temp_dir().join(format!("comodo-dbus-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let api = Api {
+            client: Client::new(dir.join("comodo.sock"), dir.join("state.sock"), dir.join("status.sock")),
+            token: None,
+            open: false,
+            hosts: vec![],
+            origins: vec![],
+        };
+        let state = UnixListener::bind(&api.client.state_path).unwrap();
+        let status = UnixListener::bind(&api.client.status_path).unwrap();
+        let (go, ready) = mpsc::channel::<()>();
+        let daemon = thread::spawn(move || {
+            let (mut stream, _) = status.accept().unwrap();
+            ready.recv().unwrap();
+            for elapsed in [10, 11] {
+                let snapshot = Snapshot { running: true, phase: Some(Phase::Focus), cycle: 1, total: 4, elapsed, length: 1500, ..Default::default() };
+                stream.write_all(format!("{}\n", serde_json::to_string(&snapshot).unwrap()).as_bytes()).unwrap();
+            }
+            // kept open until the end of the test
+            ready.recv().unwrap_or(());
+        });
+
+        serve(ConnectionBuilder::address(address.as_str()).unwrap(), api).unwrap();
+        let client = ConnectionBuilder::address(address.as_str()).unwrap().build().unwrap();
+        let timer = ProxyBuilder::<zbus::blocking::Proxy>::new_bare(&client)
+            .destination(NAME).unwrap()
+            .path(PATH).unwrap()
+            .interface(NAME).unwrap()
+            .cache_properties(CacheProperties::No)
+            .build().unwrap();
+        assert_eq!(timer.get_property::<String>("Phase").unwrap(), "");
+
+        let mut phases = timer.receive_signal("PhaseChanged").unwrap();
+        let mut ticks = timer.receive_signal("Tick").unwrap();
+        go.send(()).unwrap();
+        let (phase, cycle): (String, u64) = phases.next().unwrap().body().unwrap();
+        assert_eq!((phase.as_str(), cycle), ("focus", 1));
+        assert_eq!(ticks.next().unwrap().body::<u64>().unwrap(), 1490);
+        assert_eq!(ticks.next().unwrap().body::<u64>().unwrap(), 1489);
+        assert_eq!(timer.get_property::<u64>("Remaining").unwrap(), 1489);
+        assert_eq!(timer.get_property::<u64>("Cycle").unwrap(), 1);
+
+        timer.call_method("Pause", &("",)).unwrap();
+        let mut command = vec![];
+        state.accept().unwrap().0.read_to_end(&mut command).unwrap();
+        assert_eq!(command, PAUSE);
+        timer.call_method("Pause", &("tea",)).unwrap();
+        let mut command = vec![];
+        state.accept().unwrap().0.read_to_end(&mut command).unwrap();
+        assert_eq!(command, to_timer("tea", &PAUSE));
+        let invalid = timer.call_method("Start", &("", "25x", "", 0u8, ""));
+        assert!(invalid.unwrap_err().to_string().contains("invalid duration"));
+
+        drop(go);
+        daemon.join().unwrap();
+        bus.kill().unwrap();
+        bus.wait().unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, Utc};
+use clap::ValueEnum;
+
+use crate::{history::Record, session::Phase, stats::day_of};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Csv,
+    /// iCalendar, one VEVENT per focus block
+    Ics,
+    /// interval lines as found in the timewarrior data files
+    Timewarrior,
+    /// org-mode CLOCK entries, under a heading per task
+    Org,
+}
+
+/// A focus phase of the history, whatever its outcome
+struct Block<'a> {
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+    record: &'a Record,
+}
+
+impl Block<'_> {
+    fn title(&self) -> &str {
+        self.record.task.as_deref().unwrap_or("Pomodoro")
+    }
+}
+
+/// the focus phases started on `since` or later, the ones with broken timestamps are left out
+fn blocks(records: &[Record], since: Option<NaiveDate>) -> Vec<Block<'_>> {
+    records.iter()
+        .filter(|r| r.phase == Phase::Focus && r.actual > 0)
+        .filter(|r| since.is_none_or(|since| day_of(r).is_some_and(|day| day >= since)))
+        .filter_map(|record| {
+            let start = DateTime::parse_from_rfc3339(&record.start).ok()?;
+            let end = DateTime::parse_from_rfc3339(&record.end).ok()?;
+            Some(Block { start, end, record })
+        })
+        .collect()
+}
+
+pub fn export(records: &[Record], format: Format, since: Option<NaiveDate>) -> String {
+    let blocks = blocks(records, since);
+    match format {
+        Format::Csv => csv(&blocks),
+        Format::Ics => ics(&blocks),
+        Format::Timewarrior => timewarrior(&blocks),
+        Format::Org => org(&blocks),
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn csv(blocks: &[Block]) -> String {
+    let mut out = String::from("start,end,planned,actual,paused,outcome,profile,task,tags\n");
+    for block in blocks {
+        let record = block.record;
+        let fields = [
+            record.start.clone(),
+            record.end.clone(),
+            record.planned.to_string(),
+            record.actual.to_string(),
+            record.paused.to_string(),
+            serde_json::to_value(record.outcome).ok().and_then(|v| v.as_str().map(String::from)).unwrap_or_default(),
+            record.profile.clone(),
+            record.task.clone().unwrap_or_default(),
+            record.tags.join(";"),
+        ];
+        out += &fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(",");
+        out.push('\n');
+    }
+    out
+}
+
+fn ics_time(time: &DateTime<FixedOffset>) -> String {
+    time.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
+
+fn ics(blocks: &[Block]) -> String {
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        String::from("PRODID:-//comodo//comodo//EN"),
+    ];
+    for block in blocks {
+        let record = block.record;
+        lines.push(String::from("BEGIN:VEVENT"));
+        lines.push(format!("UID:{}-{}@comodo", ics_time(&block.start), record.cycle));
+        lines.push(format!("DTSTAMP:{}", ics_time(&block.end)));
+        lines.push(format!("DTSTART:{}", ics_time(&block.start)));
+        lines.push(format!("DTEND:{}", ics_time(&block.end)));
+        lines.push(format!("SUMMARY:{}", ics_text(block.title())));
+        if !record.tags.is_empty() {
+            lines.push(format!("CATEGORIES:{}", record.tags.iter().map(|t| ics_text(t)).collect::<Vec<_>>().join(",")));
+        }
+        lines.push(format!("DESCRIPTION:{}", ics_text(&format!(
+            "cycle {}, {} minutes of focus, {} profile", record.cycle, record.actual / 60, record.profile))));
+        lines.push(String::from("END:VEVENT"));
+    }
+    lines.push(String::from("END:VCALENDAR"));
+    // the lines of an iCalendar file end with CRLF
+    lines.iter().map(|line| format!("{}\r\n", line)).collect()
+}
+
+fn timewarrior_tag(tag: &str) -> String {
+    if tag.contains([' ', '"', '#']) || tag.is_empty() {
+        format!("\"{}\"", tag.replace('"', "\\\""))
+    } else {
+        tag.to_string()
+    }
+}
+
+fn timewarrior(blocks: &[Block]) -> String {
+    let mut out = String::new();
+    for block in blocks {
+        let tags: Vec<String> = block.record.task.iter()
+            .chain(block.record.tags.iter())
+            .map(|tag| timewarrior_tag(tag))
+            .collect();
+        let tags = if tags.is_empty() { vec![String::from("pomodoro")] } else { tags };
+        out += &format!("inc {} - {} # {}\n", ics_time(&block.start), ics_time(&block.end), tags.join(" "));
+    }
+    out
+}
+
+fn org_time(time: &DateTime<FixedOffset>) -> String {
+    time.with_timezone(&Local).format("[%Y-%m-%d %a %H:%M]").to_string()
+}
+
+fn org(blocks: &[Block]) -> String {
+    let mut tasks: BTreeMap<&str, Vec<&Block>> = BTreeMap::new();
+    for block in blocks {
+        tasks.entry(block.title()).or_default().push(block);
+    }
+    let mut out = String::new();
+    for (task, blocks) in tasks {
+        out += &format!("* {}\n  :LOGBOOK:\n", task);
+        // org lists the latest clock first
+        for block in blocks.iter().rev() {
+            let minutes = block.record.actual / 60;
+            out += &format!("  CLOCK: {}--{} => {:>2}:{:02}\n",
+                            org_time(&block.start), org_time(&block.end), minutes / 60, minutes % 60);
+        }
+        out += "  :END:\n";
+    }
+    out
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use crate::{history::Record, session::Phase, stats::parse_date};
+    #[allow(unused_imports)]
+    use super::{export, Format};
+
+    #[allow(dead_code)]
+    fn records() -> Vec<Record> {
+        let record = |day: &str, phase: Phase, task: Option<&str>, tags: &[&str]| Record {
+            phase,
+            cycle: 2,
+            end: format!("2026-10-{}T09:25:00+00:00", day),
+            profile: String::from("work"),
+            task: task.map(String::from),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Record::sample(&format!("2026-10-{}T09:00:00+00:00", day))
+        };
+        vec![
+            record("01", Phase::Focus, Some("acme, invoice"), &["billable", "acme corp"]),
+            record("01", Phase::ShortBreak, None, &[]),
+            record("05", Phase::Focus, None, &[]),
+        ]
+    }
+
+    #[test]
+    fn csv_test() {
+        let out = export(&records(), Format::Csv, None);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], r#"2026-10-01T09:00:00+00:00,2026-10-01T09:25:00+00:00,1500,1500,0,completed,work,"acme, invoice",billable;acme corp"#);
+
+        let since = export(&records(), Format::Csv, Some(parse_date("2026-10-03").unwrap()));
+        assert_eq!(since.lines().count(), 2);
+        assert!(since.contains("2026-10-05"));
+    }
+
+    #[test]
+    fn ics_test() {
+        let out = export(&records(), Format::Ics, None);
+        assert!(out.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(out.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(out.matches("BEGIN:VEVENT").count(), 2);
+        assert!(out.contains("DTSTART:20261001T090000Z\r\nDTEND:20261001T092500Z\r\n"));
+        assert!(out.contains("SUMMARY:acme\\, invoice\r\n"));
+        assert!(out.contains("CATEGORIES:billable,acme corp\r\n"));
+        assert!(out.contains("SUMMARY:Pomodoro\r\n"));
+    }
+
+    #[test]
+    fn timewarrior_test() {
+        let out = export(&records(), Format::Timewarrior, None);
+        assert_eq!(out, "inc 20261001T090000Z - 20261001T092500Z # \"acme, invoice\" billable \"acme corp\"\n\
+                         inc 20261005T090000Z - 20261005T092500Z # pomodoro\n");
+    }
+
+    #[test]
+    fn org_test() {
+        let out = export(&records(), Format::Org, None);
+        assert!(out.starts_with("* Pomodoro\n  :LOGBOOK:\n  CLOCK: [2026-10-0"));
+        assert!(out.contains("* acme, invoice\n"));
+        assert_eq!(out.matches(" =>  0:25\n").count(), 2);
+        assert_eq!(out.matches("  :END:\n").count(), 2);
+    }
+}
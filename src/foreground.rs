@@ -0,0 +1,163 @@
+use std::{
+    io::{stdout, IsTerminal, Write},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    thread, time::Duration,
+};
+
+use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal};
+
+use crate::{
+    as_time, now_in_secs,
+    config::Config,
+    history::{self, Tracker},
+    session::{Event, Session, Status},
+};
+
+/// set by ctrl-c
+static STOPPED: AtomicBool = AtomicBool::new(false);
+/// ctrl-z presses not handled yet, each one toggles the pause
+static TOGGLES: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    if signal == libc::SIGINT {
+        STOPPED.store(true, Ordering::SeqCst);
+    } else {
+        TOGGLES.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// `25m`, `90s`, `1h30m` or `25:00`, a bare number is in minutes
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration {:?}, expected something like 25m, 90s, 1h30m or 25:00", s);
+    if let Some((minutes, seconds)) = s.split_once(':') {
+        let minutes = minutes.parse::<u64>().map_err(|_| invalid())?;
+        let seconds = seconds.parse::<u64>().map_err(|_| invalid())?;
+        return Ok(Duration::from_secs(minutes * 60 + seconds));
+    }
+    if let Ok(minutes) = s.parse::<u64>() {
+        return Ok(Duration::from_secs(minutes * 60));
+    }
+
+    let (mut total, mut number) = (0, String::new());
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        total += number.parse::<u64>().map_err(|_| invalid())? * unit;
+        number.clear();
+    }
+    if !number.is_empty() || s.is_empty() {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(total))
+}
+
+/// the countdown line
+fn line(status: &Status) -> String {
+    format!("{} {}/{}  {}{}",
+            status.phase.name(), status.cycle, status.total,
+            as_time(status.length.saturating_sub(status.elapsed).as_secs()),
+            if status.paused { "  (paused)" } else { "" })
+}
+
+/// Runs a session right there in the terminal, without the daemon, until it
+/// completes or gets stopped with ctrl-c, ctrl-z pauses and resumes it
+pub fn run(config: &Config) -> Result<(), String> {
+    let action = SigAction::new(SigHandler::Handler(on_signal), SaFlags::SA_RESTART, SigSet::empty());
+    for sig in [Signal::SIGINT, Signal::SIGTSTP] {
+        // the handler only touches atomics
+        unsafe { signal::sigaction(sig, &action) }.map_err(|e| format!("could not handle {}: {}", sig, e))?;
+    }
+
+    let tty = stdout().is_terminal();
+    let mut out = stdout();
+    let mut session = Session::new(&config.comodo, now_in_secs());
+    let mut history = Tracker::new(config.comodo.profile.as_deref().unwrap_or("default"),
+                                   config.task.clone(), config.tags.clone());
+    eprintln!("ctrl-c to stop, ctrl-z to pause or resume");
+
+    loop {
+        let now = now_in_secs();
+        let mut events = vec![];
+
+        if STOPPED.swap(false, Ordering::SeqCst) {
+            history::record(history.stop(now));
+            if tty {
+                println!();
+            }
+            println!("stopped");
+            return Ok(());
+        }
+        for _ in 0..TOGGLES.swap(0, Ordering::SeqCst) {
+            events.extend(if session.is_paused() { session.resume(now) } else { session.pause(now) });
+        }
+        events.extend(session.tick(now));
+
+        for event in events {
+            history::record(history.event(event, session.timing(event, now)));
+            match (event, session.status(now)) {
+                (Event::SessionComplete, _) => {
+                    if tty {
+                        println!();
+                    }
+                    println!("session complete, {} pomodoros", session.total());
+                    return Ok(());
+                },
+                // piped, a line per change rather than a line redrawn in place
+                (_, Some(status)) if !tty => println!("{}", line(&status)),
+                _ => {},
+            }
+        }
+
+        if let (true, Some(status)) = (tty, session.status(now)) {
+            print!("\r\x1b[K{}", line(&status));
+            out.flush().map_err(|e| e.to_string())?;
+        }
+        thread::sleep(Duration::from_millis(200));
+    }
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use std::time::Duration;
+    #[allow(unused_imports)]
+    use crate::session::{Phase, Status};
+    #[allow(unused_imports)]
+    use super::{line, parse_duration};
+
+    #[test]
+    fn parse_duration_test() {
+        assert_eq!(parse_duration("25m"), Ok(Duration::from_secs(1500)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("05:30"), Ok(Duration::from_secs(330)));
+        assert_eq!(parse_duration("5"), Ok(Duration::from_secs(300)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("25").is_ok());
+        assert!(parse_duration("25x").is_err());
+        assert!(parse_duration("1h30").is_err());
+        assert!(parse_duration("m").is_err());
+    }
+
+    #[test]
+    fn line_test() {
+        let mut status = Status {
+            phase: Phase::Focus,
+            cycle: 2,
+            total: 4,
+            elapsed: Duration::from_secs(60),
+            length: Duration::from_secs(1500),
+            paused: false,
+        };
+        assert_eq!(line(&status), "focus 2/4  24:00");
+        status.paused = true;
+        assert_eq!(line(&status), "focus 2/4  24:00  (paused)");
+    }
+}
@@ -0,0 +1,414 @@
+use std::{
+    env, fs::{self, File, OpenOptions}, io::{BufRead, BufReader, ErrorKind, Write}, os::fd::AsRawFd,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Local, SecondsFormat, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::session::{Event, Phase, Timing};
+
+/// bumped whenever a field changes meaning or goes away, adding one does not need it
+pub const VERSION: u32 = 1;
+
+/// `$<variable>/comodo`, or `~/<fallback>/comodo` when it is not set
+pub fn xdg_dir(variable: &str, fallback: &str) -> Option<PathBuf> {
+    let base = match env::var_os(variable).filter(|v| !v.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(fallback),
+    };
+    Some(base.join("comodo"))
+}
+
+/// `$XDG_DATA_HOME/comodo`, or `~/.local/share/comodo` when it is not set
+pub fn data_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// written aside and renamed over, a crash or a reader in the middle of it
+/// only ever sees the previous content or the new one
+pub fn replace(path: &Path, content: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("could not create {}: {}", dir.display(), e))?;
+    }
+    let temporary = path.with_extension("json.tmp");
+    fs::write(&temporary, content).map_err(|e| format!("could not write {}: {}", temporary.display(), e))?;
+    fs::rename(&temporary, path).map_err(|e| format!("could not write {}: {}", path.display(), e))
+}
+
+/// a file already gone is fine
+pub fn remove(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        if e.kind() != ErrorKind::NotFound {
+            eprintln!("could not remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+pub fn path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("history.jsonl"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Completed,
+    Skipped,
+    /// the session got stopped during the phase
+    Interrupted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interruption {
+    /// RFC 3339 in local time
+    pub at: String,
+    /// someone else's doing rather than one's own
+    pub external: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// A line of the history, one per phase that ended
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    pub version: u32,
+    pub phase: Phase,
+    pub cycle: u64,
+    pub outcome: Outcome,
+    /// RFC 3339 in local time
+    pub start: String,
+    pub end: String,
+    /// the seconds the phase was meant to last
+    pub planned: u64,
+    /// the seconds it actually ran for, pauses excluded
+    pub actual: u64,
+    pub paused: u64,
+    pub profile: String,
+    #[serde(default)]
+    pub task: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub interruptions: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interruption_log: Vec<Interruption>,
+}
+
+impl Record {
+    /// a completed focus block of 25 minutes started at `start`, for the tests
+    /// to override what they look at
+    pub fn sample(start: &str) -> Self {
+        Record {
+            version: VERSION,
+            phase: Phase::Focus,
+            cycle: 1,
+            outcome: Outcome::Completed,
+            start: start.to_string(),
+            end: start.to_string(),
+            planned: 1500,
+            actual: 1500,
+            paused: 0,
+            profile: String::from("default"),
+            task: None,
+            tags: vec![],
+            interruptions: 0,
+            interruption_log: vec![],
+        }
+    }
+}
+
+/// appends `record` as a single line, the file is locked meanwhile so
+/// several daemons can share it
+pub fn append(path: &PathBuf, record: &Record) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("could not create {}: {}", dir.display(), e))?;
+    }
+    let mut line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+    line.push('\n');
+
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .map_err(|e| format!("could not open {}: {}", path.display(), e))?;
+    let fd = file.as_raw_fd();
+    if unsafe { libc::flock(fd, libc::LOCK_EX) } != 0 {
+        return Err(format!("could not lock {}", path.display()));
+    }
+    // one write of the whole line, a reader never sees half of it
+    let written = file.write_all(line.as_bytes()).map_err(|e| e.to_string());
+    unsafe { libc::flock(fd, libc::LOCK_UN) };
+    written
+}
+
+/// appends `record` to the history of the user, when there is one, the
+/// failures are only reported
+pub fn record(record: Option<Record>) {
+    let Some(record) = record else { return };
+    let Some(path) = path() else {
+        eprintln!("no place to keep the history, neither XDG_DATA_HOME nor HOME is set");
+        return;
+    };
+    if let Err(e) = append(&path, &record) {
+        eprintln!("could not record the {} phase: {}", record.phase.name(), e);
+    }
+}
+
+/// every record of the history, a missing file is an empty history and
+/// the lines that can not be parsed are reported and skipped
+pub fn read(path: &PathBuf) -> Result<Vec<Record>, String> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("could not open {}: {}", path.display(), e)),
+    };
+    let mut records = vec![];
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(e) => eprintln!("{}:{}: skipped, {}", path.display(), number + 1, e),
+        }
+    }
+    Ok(records)
+}
+
+fn timestamp(secs: u64) -> String {
+    Local.timestamp_opt(secs as i64, 0)
+        .single()
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+/// the phase being recorded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Current {
+    phase: Phase,
+    cycle: u64,
+    start: u64,
+    planned: u64,
+    paused: u64,
+    paused_at: Option<u64>,
+    skipped: bool,
+    interruptions: Vec<Interruption>,
+}
+
+/// Follows the events of a session and turns every phase that ends into a [`Record`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tracker {
+    profile: String,
+    task: Option<String>,
+    tags: Vec<String>,
+    current: Option<Current>,
+}
+
+impl Tracker {
+    pub fn new(profile: &str, task: Option<String>, tags: Vec<String>) -> Self {
+        Tracker { profile: profile.to_string(), task, tags, current: None }
+    }
+
+    fn finish(&mut self, now: u64, outcome: Outcome) -> Option<Record> {
+        let current = self.current.take()?;
+        let paused = current.paused + current.paused_at.map_or(0, |at| now.saturating_sub(at));
+        Some(Record {
+            version: VERSION,
+            phase: current.phase,
+            cycle: current.cycle,
+            outcome: if current.skipped { Outcome::Skipped } else { outcome },
+            start: timestamp(current.start),
+            end: timestamp(now),
+            planned: current.planned,
+            actual: now.saturating_sub(current.start).saturating_sub(paused),
+            paused,
+            profile: self.profile.clone(),
+            task: self.task.clone(),
+            tags: self.tags.clone(),
+            interruptions: current.interruptions.len() as u32,
+            interruption_log: current.interruptions,
+        })
+    }
+
+    /// `timing` is when the event happened, see [`crate::session::Session::timing`],
+    /// the record of the phase it ended if any
+    pub fn event(&mut self, event: Event, timing: Timing) -> Option<Record> {
+        let now = timing.at;
+        match event {
+            Event::PhaseStarted { phase, cycle } => {
+                let record = self.finish(now, Outcome::Completed);
+                self.current = Some(Current {
+                    phase,
+                    cycle,
+                    start: now,
+                    planned: timing.planned.as_secs(),
+                    paused: 0,
+                    paused_at: None,
+                    skipped: false,
+                    interruptions: vec![],
+                });
+                record
+            },
+            Event::Paused => {
+                if let Some(current) = self.current.as_mut() {
+                    current.paused_at = Some(now);
+                }
+                None
+            },
+            Event::Resumed => {
+                if let Some(current) = self.current.as_mut() {
+                    current.paused += current.paused_at.take().map_or(0, |at| now.saturating_sub(at));
+                }
+                None
+            },
+            Event::SessionComplete => self.finish(now, Outcome::Completed),
+        }
+    }
+
+    /// for the phases ending from now on, the current one included
+    pub fn set_task(&mut self, task: Option<String>, tags: Vec<String>) {
+        self.task = task;
+        self.tags = tags;
+    }
+
+    /// the current phase is recorded as skipped once the next one starts
+    pub fn skip(&mut self) {
+        if let Some(current) = self.current.as_mut() {
+            current.skipped = true;
+        }
+    }
+
+    /// counted as a pause, see [`crate::session::Session::downtime`]
+    pub fn downtime(&mut self, by: u64) {
+        if let Some(current) = self.current.as_mut().filter(|c| c.paused_at.is_none()) {
+            current.paused += by;
+        }
+    }
+
+    /// counted against the current focus phase, `false` when not focusing
+    pub fn interrupt(&mut self, now: u64, external: bool, note: Option<String>) -> bool {
+        match self.current.as_mut() {
+            Some(current) if current.phase == Phase::Focus => {
+                current.interruptions.push(Interruption { at: timestamp(now), external, note });
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// of the current phase
+    /// whether the phase running was skipped rather than gone through
+    pub fn skipped(&self) -> bool {
+        self.current.as_ref().is_some_and(|c| c.skipped)
+    }
+
+    pub fn interruptions(&self) -> u32 {
+        self.current.as_ref().map_or(0, |c| c.interruptions.len() as u32)
+    }
+
+    pub fn stop(&mut self, now: u64) -> Option<Record> {
+        self.finish(now, Outcome::Interrupted)
+    }
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use std::{fs, time::Duration};
+    #[allow(unused_imports)]
+    use crate::config::Config;
+    #[allow(unused_imports)]
+    use crate::session::{Event, Phase, Session, Timing};
+    #[allow(unused_imports)]
+    use super::{append, read, Outcome, Record, Tracker};
+
+    #[allow(dead_code)]
+    fn timing(at: u64, planned: u64) -> Timing {
+        Timing { at, planned: Duration::from_secs(planned) }
+    }
+
+    #[test]
+    fn tracker_test() {
+        let mut tracker = Tracker::new("work", Some(String::from("report")), vec![String::from("writing")]);
+        assert_eq!(tracker.event(Event::PhaseStarted { phase: Phase::Focus, cycle: 1 }, timing(1000, 1500)), None);
+        assert_eq!(tracker.event(Event::Paused, timing(1100, 1500)), None);
+        assert_eq!(tracker.event(Event::Resumed, timing(1160, 1500)), None);
+        assert!(tracker.interrupt(1200, true, Some(String::from("phone call"))));
+        assert!(tracker.interrupt(1300, false, None));
+        assert_eq!(tracker.interruptions(), 2);
+
+        let focus = tracker.event(Event::PhaseStarted { phase: Phase::ShortBreak, cycle: 1 }, timing(2560, 300)).unwrap();
+        assert_eq!((focus.phase, focus.cycle, focus.outcome), (Phase::Focus, 1, Outcome::Completed));
+        assert_eq!((focus.planned, focus.actual, focus.paused), (1500, 1500, 60));
+        assert_eq!((focus.profile.as_str(), focus.task.as_deref()), ("work", Some("report")));
+        assert_eq!(focus.tags, vec![String::from("writing")]);
+        assert_eq!(focus.interruptions, 2);
+        assert_eq!((focus.interruption_log[0].external, focus.interruption_log[0].note.as_deref()), (true, Some("phone call")));
+        assert_eq!(tracker.interruptions(), 0);
+        // breaks are not interrupted
+        assert!(!tracker.interrupt(2570, true, None));
+
+        assert!(!tracker.skipped());
+        tracker.skip();
+        assert!(tracker.skipped());
+        let rest = tracker.event(Event::PhaseStarted { phase: Phase::Focus, cycle: 2 }, timing(2600, 1500)).unwrap();
+        assert_eq!((rest.outcome, rest.planned, rest.actual), (Outcome::Skipped, 300, 40));
+
+        // stopped while paused, the pause is counted up to the stop
+        tracker.event(Event::Paused, timing(2700, 0));
+        let stopped = tracker.stop(2800).unwrap();
+        assert_eq!((stopped.outcome, stopped.actual, stopped.paused), (Outcome::Interrupted, 100, 100));
+        assert_eq!(tracker.stop(2900), None);
+    }
+
+    #[test]
+    fn crossed_test() {
+        // the daemon slept through the end of the first focus and the whole break
+        let config: Config = toml::de::from_str(r#"
+            [comodo]
+            iterations = 2
+            focus = "01:40"
+            rest = "00:20"
+            big_rest = "00:30"
+            long_break_interval = 4
+        "#).unwrap();
+        let mut session = Session::new(&config.comodo, 1000);
+        let mut tracker = Tracker::new("default", None, vec![]);
+        let mut records = vec![];
+        for now in [1000, 1150] {
+            for event in session.tick(now) {
+                records.extend(tracker.event(event, session.timing(event, now)));
+            }
+        }
+        let rows: Vec<_> = records.iter().map(|r| (r.phase, r.planned, r.actual)).collect();
+        assert_eq!(rows, vec![(Phase::Focus, 100, 100), (Phase::ShortBreak, 20, 20)]);
+        assert_eq!(records[1].start, super::timestamp(1100));
+    }
+
+    #[test]
+    fn append_test() {
+        let path = std::env::temp_dir().join(format!("comodo-history-{}", std::process::id())).join("history.jsonl");
+        let mut tracker = Tracker::new("default", None, vec![]);
+        tracker.event(Event::PhaseStarted { phase: Phase::Focus, cycle: 1 }, timing(0, 60));
+        let record = tracker.event(Event::SessionComplete, timing(60, 0)).unwrap();
+
+        // appended from several threads at once, every line stays whole
+        let threads: Vec<_> = (0..8).map(|_| {
+            let (path, record) = (path.clone(), record.clone());
+            std::thread::spawn(move || append(&path, &record).unwrap())
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(read(&path).unwrap(), vec![record.clone(); 8]);
+        assert!(content.lines().next().unwrap().starts_with(r#"{"version":1,"phase":"focus","cycle":1,"outcome":"completed","start":"19"#));
+
+        // a torn or foreign line does not hide the rest of the history
+        fs::write(&path, format!("not json\n{}\n", content.lines().next().unwrap())).unwrap();
+        assert_eq!(read(&path).unwrap(), vec![record]);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(read(&path).unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    io::Read, os::unix::process::CommandExt, process::{Command, ExitStatus, Stdio},
+    sync::mpsc::{self, Receiver, Sender}, thread, time::{Duration, Instant},
+};
+
+use crate::{config::Hooks, session::{Event, Phase}};
+
+/// The moments of a session a `[hooks]` command can be attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    FocusStart,
+    BreakStart,
+    LongBreakStart,
+    Pause,
+    Resume,
+    SessionEnd,
+    Stop,
+}
+
+impl Hook {
+    pub fn for_event(event: Event) -> Hook {
+        match event {
+            Event::PhaseStarted { phase: Phase::Focus, .. } => Hook::FocusStart,
+            Event::PhaseStarted { phase: Phase::ShortBreak, .. } => Hook::BreakStart,
+            Event::PhaseStarted { phase: Phase::LongBreak, .. } => Hook::LongBreakStart,
+            Event::Paused => Hook::Pause,
+            Event::Resumed => Hook::Resume,
+            Event::SessionComplete => Hook::SessionEnd,
+        }
+    }
+
+    /// the key in the `[hooks]` table
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hook::FocusStart => "on_focus_start",
+            Hook::BreakStart => "on_break_start",
+            Hook::LongBreakStart => "on_long_break_start",
+            Hook::Pause => "on_pause",
+            Hook::Resume => "on_resume",
+            Hook::SessionEnd => "on_session_end",
+            Hook::Stop => "on_stop",
+        }
+    }
+
+    pub fn command(self, hooks: &Hooks) -> Option<&str> {
+        match self {
+            Hook::FocusStart => hooks.on_focus_start.as_deref(),
+            Hook::BreakStart => hooks.on_break_start.as_deref(),
+            Hook::LongBreakStart => hooks.on_long_break_start.as_deref(),
+            Hook::Pause => hooks.on_pause.as_deref(),
+            Hook::Resume => hooks.on_resume.as_deref(),
+            Hook::SessionEnd => hooks.on_session_end.as_deref(),
+            Hook::Stop => hooks.on_stop.as_deref(),
+        }
+    }
+}
+
+/// What the session looked like when the hook fired, handed over as `COMODO_*` variables
+#[derive(Debug, Clone, Default)]
+pub struct Env {
+    pub timer: String,
+    pub phase: Option<Phase>,
+    pub cycle: u64,
+    pub total: u64,
+    pub task: String,
+    /// seconds left in the phase
+    pub remaining: u64,
+}
+
+impl Env {
+    pub fn vars(&self, hook: Hook) -> Vec<(&'static str, String)> {
+        vec![
+            ("COMODO_EVENT", hook.name().to_string()),
+            ("COMODO_TIMER", self.timer.clone()),
+            ("COMODO_PHASE", self.phase.map(|p| p.id()).unwrap_or_default().to_string()),
+            ("COMODO_CYCLE", self.cycle.to_string()),
+            ("COMODO_TOTAL", self.total.to_string()),
+            ("COMODO_TASK", self.task.clone()),
+            ("COMODO_REMAINING", self.remaining.to_string()),
+        ]
+    }
+}
+
+/// How a hook went, `status` is `None` when it got killed for running too long
+#[derive(Debug)]
+pub struct Outcome {
+    pub status: Option<ExitStatus>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut output = String::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_string(&mut output).unwrap_or(0);
+        }
+        output
+    })
+}
+
+/// runs `command` through `sh -c` and waits for it, for at most `timeout`
+pub fn execute(command: &str, vars: &[(&str, String)], timeout: Duration) -> Result<Outcome, String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(vars.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // its own process group, so that what the hook started goes with it on timeout
+        .process_group(0)
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    // read while waiting, a hook writing more than a pipe holds would never end otherwise
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+            break Some(status);
+        }
+        if started.elapsed() >= timeout {
+            unsafe { libc::kill(-(child.id() as i32), libc::SIGKILL) };
+            child.wait().unwrap_or_else(|_| ExitStatus::default());
+            break None;
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+
+    Ok(Outcome {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// A hook waiting for its turn on the hook thread
+struct Job {
+    hook: Hook,
+    command: String,
+    vars: Vec<(&'static str, String)>,
+    timeout: Duration,
+}
+
+// one hook at a time, in the order they were queued
+fn work(receiver: Receiver<Job>) {
+    for job in receiver {
+        let name = job.hook.name();
+        match execute(&job.command, &job.vars, job.timeout) {
+            Ok(outcome) => {
+                for line in outcome.stdout.lines() {
+                    println!("{}: {}", name, line);
+                }
+                for line in outcome.stderr.lines() {
+                    eprintln!("{}: {}", name, line);
+                }
+                match outcome.status {
+                    Some(status) if !status.success() => eprintln!("{} failed: {}", name, status),
+                    Some(_) => (),
+                    None => eprintln!("{} killed after {}s", name, job.timeout.as_secs()),
+                }
+            },
+            Err(e) => eprintln!("could not run {}: {}", name, e),
+        }
+    }
+}
+
+/// Handle to the hook thread, the hooks run there one after the other so
+/// that they see the events in the order they happened
+#[derive(Debug, Clone)]
+pub struct Runner {
+    sender: Sender<Job>,
+}
+
+impl Runner {
+    /// must be called after daemonizing, the thread would not survive the fork
+    pub fn spawn() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::Builder::new()
+            .name(String::from("hooks"))
+            .spawn(move || work(receiver))
+            .unwrap();
+        Runner { sender }
+    }
+
+    /// queues the hook configured for `hook` if any without blocking the
+    /// daemon, its output ends up in the daemon log
+    pub fn run(&self, hooks: &Hooks, hook: Hook, env: &Env) {
+        let Some(command) = hook.command(hooks) else { return };
+        let job = Job {
+            hook,
+            command: command.to_string(),
+            vars: env.vars(hook),
+            timeout: Duration::from_secs(hooks.timeout as u64),
+        };
+        if self.sender.send(job).is_err() {
+            eprintln!("hook thread is gone, not running {}", hook.name());
+        }
+    }
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use std::time::{Duration, Instant};
+    #[allow(unused_imports)]
+    use crate::{config::Hooks, session::{Event, Phase}};
+    #[allow(unused_imports)]
+    use super::{execute, Env, Hook, Runner};
+
+    #[test]
+    fn command_test() {
+        let hooks = Hooks { on_long_break_start: Some(String::from("true")), ..Default::default() };
+        let hook = Hook::for_event(Event::PhaseStarted { phase: Phase::LongBreak, cycle: 4 });
+        assert_eq!(hook, Hook::LongBreakStart);
+        assert_eq!(hook.command(&hooks), Some("true"));
+        assert_eq!(Hook::for_event(Event::SessionComplete).command(&hooks), None);
+    }
+
+    #[test]
+    fn env_test() {
+        let env = Env { timer: String::from("default"), phase: Some(Phase::ShortBreak), cycle: 2, total: 4, task: String::from("report"), remaining: 300 };
+        let outcome = execute(
+            "echo $COMODO_EVENT $COMODO_PHASE $COMODO_CYCLE/$COMODO_TOTAL $COMODO_TASK $COMODO_REMAINING; echo oops >&2",
+            &env.vars(Hook::BreakStart),
+            Duration::from_secs(5),
+        ).unwrap();
+        assert!(outcome.status.unwrap().success());
+        assert_eq!(outcome.stdout, "on_break_start short_break 2/4 report 300\n");
+        assert_eq!(outcome.stderr, "oops\n");
+    }
+
+    #[test]
+    fn timeout_test() {
+        let started = Instant::now();
+        let outcome = execute("echo early; sleep 10", &[], Duration::from_millis(300)).unwrap();
+        assert!(outcome.status.is_none());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn order_test() {
+        let path = std::env::temp_dir().join(format!("comodo-hooks-{}", std::process::id()));
+        let hooks = Hooks {
+            on_pause: Some(format!("sleep 0.3; echo pause >> {}", path.display())),
+            on_resume: Some(format!("echo resume >> {}", path.display())),
+            ..Default::default()
+        };
+        let runner = Runner::spawn();
+        runner.run(&hooks, Hook::Pause, &Env::default());
+        runner.run(&hooks, Hook::Resume, &Env::default());
+
+        // the slow one is not overtaken
+        let started = Instant::now();
+        while std::fs::read_to_string(&path).unwrap_or_default().lines().count() < 2 && started.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "pause\nresume\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
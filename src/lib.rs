@@ -0,0 +1,101 @@
+//! The config, the session engine and the protocol of the comodo daemon,
+//! with a [`Client`](client::Client) to control it from Rust
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use config::{Ambient, Colors, Comodo, Config, Hooks, Notifications, Restore, Sounds};
+
+pub mod ambient;
+pub mod api;
+pub mod big;
+pub mod builtin;
+pub mod checkpoint;
+pub mod client;
+pub mod config;
+pub mod daemon;
+pub mod dbus;
+pub mod export;
+pub mod foreground;
+pub mod history;
+pub mod hooks;
+pub mod notifier;
+pub mod player;
+pub mod prompt;
+pub mod protocol;
+pub mod session;
+pub mod snapshot;
+pub mod stats;
+pub mod tasks;
+pub mod template;
+pub mod tui;
+
+pub fn as_time(seconds: u64) -> String{
+    let left_minutes = if seconds / 60 < 10 {
+        format!("0{}", seconds/60)
+    }else {
+        format!("{}", seconds/60)
+    };
+
+    let left_seconds = if seconds % 60 < 10 {
+        format!("0{}", seconds % 60)
+    }else {
+        format!("{}", seconds % 60)
+    };
+
+    format!("{}:{}",left_minutes, left_seconds)
+}
+
+pub fn now_in_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+
+/// the config of a session started from the command line rather than a file
+pub fn cli_config(number: u8, focus: Duration, rest: Duration) -> Config {
+    Config {
+        comodo: Comodo {
+            iterations: number,
+            focus,
+            rest,
+            big_rest: Duration::from_secs(900),
+            popup_notification: true,
+            sound_notification: false,
+            focus_notification_banner: String::from("start focusing!"),
+            rest_notification_banner: String::from("rest steady!"),
+            end_notification_banner: String::from("End of Session!"),
+            notification_summary: String::from("Comodo Pomodoro"),
+            focus_audio_notification_path: None,
+            rest_audio_notification_path: None,
+            long_break_interval: 4,
+            audio_device: None,
+            profile: None,
+            restore: Restore::default(),
+        },
+        sounds: Sounds::default(),
+        ambient: Ambient::default(),
+        notifications: Notifications::default(),
+        hooks: Hooks::default(),
+        colors: Colors::default(),
+        http: None,
+        task: None,
+        tags: vec![],
+    }
+}
+
+/// sets what the session is spent on, an id from the task list brings the
+/// name and the tags of the task along
+pub fn with_task(config: &mut Config, task: Option<String>, tags: Vec<String>) -> Result<(), String> {
+    if let Some(task) = task {
+        let (name, task_tags) = tasks::resolve(&task)?;
+        config.task = Some(name);
+        config.tags = task_tags;
+    }
+    for tag in tags {
+        if !config.tags.contains(&tag) {
+            config.tags.push(tag);
+        }
+    }
+    Ok(())
+}
+
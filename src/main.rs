@@ -1,27 +1,23 @@
 use std::{
-    fs::{File, self}, env, os::unix::net::{UnixStream, UnixListener},
-    io::{Write, Read}, path::{Path, PathBuf},
-    net::{TcpStream, TcpListener},
-    time::{Duration, SystemTime, UNIX_EPOCH}, thread, 
+    fs::{self, File, OpenOptions}, env, os::unix::net::UnixStream,
+    io::{BufRead, BufReader, IsTerminal, Write}, path::Path,
+    time::Duration,
 };
 
-use config::{Comodo, Config};
-use sysinfo::System;
-use notify_rust::{Notification, Timeout};
+use comodo::{
+    big, builtin, checkpoint, cli_config, daemon, export, foreground, history, now_in_secs, player, prompt, stats,
+    tasks, tui, with_task,
+    client::Client,
+    config::{Config, Restore},
+    notifier::Message,
+    protocol::{
+        parse_timer, ACK, DEFAULT_TIMER, INTERRUPT, INTERRUPT_EXTERNAL, INTERRUPT_PAUSE, KILL,
+        SOCKET_PATH, STATE_PATH, STATUS_PATH, TASK, WATCH_PATH,
+    },
+};
 
 use clap::{Parser, Subcommand};
 use daemonize::Daemonize;
-mod config;
-mod player;
-
-const DEFAULT: [u8;1] = [0];
-const PAUSE: [u8;1] = [1];
-const RESUME: [u8;1] = [2];
-const STOP: [u8;1] = [3];
-const KILL: [u8;1] = [4];
-const OFF: [u8;1] = [0];
-const ON: [u8;1] = [1];
-
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
@@ -34,11 +30,28 @@ pub struct Cli {
 #[derive(Subcommand, Debug)]
 pub enum State {
     /// initiate a backgroud daemon if it does not exist
-    Init,
+    Init {
+        #[arg(long)]
+        /// Pick up the session a previous daemon died in the middle of without asking
+        restore: bool,
+        #[arg(long, conflicts_with = "restore")]
+        /// Forget about the session a previous daemon died in the middle of
+        discard: bool,
+        #[arg(short, long)]
+        /// Config file to take the daemon settings from, the `[http]` table
+        config: Option<String>,
+    },
     /// Kills the backgroud daemon if it exist
     Kill,
-    Pause,
+    Pause {
+        #[arg(value_parser = parse_timer, default_value = DEFAULT_TIMER)]
+        /// The timer, the one started without `--name` when missing
+        timer: String,
+    },
     Start {
+        #[arg(long = "name", value_parser = parse_timer, default_value = DEFAULT_TIMER)]
+        /// Run it as a timer of its own, next to the sessions of the other names
+        timer: String,
         #[arg(short, long, default_value_t = 5)]
         /// Pomodoro duration in seconds
         focus: u64,
@@ -50,151 +63,416 @@ pub enum State {
         number: u8,
         #[arg(short, long, default_value_t = String::new())]
         /// Path for the config file
-        config: String
+        config: String,
+        #[arg(long)]
+        /// What the session is spent on, a free label or the id of a task of `comodo task list`
+        task: Option<String>,
+        #[arg(long = "tag")]
+        /// Can be given several times
+        tags: Vec<String>,
+    },
+    /// Run a session in the foreground without the daemon, ctrl-c stops it and ctrl-z pauses it
+    Run {
+        #[arg(short, long, default_value = "25m", value_parser = foreground::parse_duration)]
+        /// Focus duration, as 25m, 90s, 1h30m or 25:00
+        focus: Duration,
+        #[arg(short, long, default_value = "5m", value_parser = foreground::parse_duration)]
+        /// Rest duration
+        rest: Duration,
+        #[arg(short, long, default_value = "15m", value_parser = foreground::parse_duration)]
+        /// Long rest duration
+        long_rest: Duration,
+        #[arg(short, long, default_value_t = 4)]
+        /// Number of cycles
+        number: u8,
+        #[arg(long)]
+        /// What the session is spent on, a free label or the id of a task of `comodo task list`
+        task: Option<String>,
+        #[arg(long = "tag")]
+        /// Can be given several times
+        tags: Vec<String>,
     },
-    Stop,
-    Resume,
-    Status,
+    Stop {
+        #[arg(value_parser = parse_timer, default_value = DEFAULT_TIMER)]
+        /// The timer, the one started without `--name` when missing
+        timer: String,
+    },
+    Resume {
+        #[arg(value_parser = parse_timer, default_value = DEFAULT_TIMER)]
+        /// The timer, the one started without `--name` when missing
+        timer: String,
+    },
+    /// End the current phase now and move on to the next one
+    Skip {
+        #[arg(value_parser = parse_timer, default_value = DEFAULT_TIMER)]
+        /// The timer, the one started without `--name` when missing
+        timer: String,
+    },
+    /// Make the current phase longer
+    Extend {
+        #[arg(default_value_t = 5)]
+        /// Minutes to add
+        minutes: u64,
+        #[arg(long = "name", value_parser = parse_timer, default_value = DEFAULT_TIMER)]
+        /// The timer, the one started without `--name` when missing
+        timer: String,
+    },
+    /// Count an interruption against the current pomodoro
+    Interrupt {
+        #[arg(short, long)]
+        /// Caused by someone else rather than by yourself
+        external: bool,
+        #[arg(short, long)]
+        /// Pause the timer as well
+        pause: bool,
+        /// What it was about
+        note: Option<String>,
+        #[arg(long = "name", value_parser = parse_timer, default_value = DEFAULT_TIMER)]
+        /// The timer, the one started without `--name` when missing
+        timer: String,
+    },
+    /// Silence the sounds that repeat until acknowledged
+    Ack,
+    /// Print the notifications sent to the `clients` backend as they come
+    Watch,
+    /// Every running timer, or the one given
+    Status {
+        #[arg(value_parser = parse_timer)]
+        timer: Option<String>,
+        #[arg(short, long)]
+        /// The remaining time in large digits colored by phase, as large as the terminal allows
+        big: bool,
+        #[arg(short, long, requires = "big")]
+        /// Keep redrawing the big clock as the time goes
+        follow: bool,
+    },
+    /// Follow the running session full screen, with keys to drive it
+    Tui {
+        #[arg(value_parser = parse_timer, default_value = DEFAULT_TIMER)]
+        /// The timer, the one started without `--name` when missing
+        timer: String,
+    },
+    /// Print what to add to the config of a shell to get the timer in its prompt
+    Prompt {
+        #[arg(value_enum)]
+        shell: prompt::Shell,
+    },
+    /// The timer as shown in the prompt, nothing when no session is running
+    PromptSegment,
     From,
+    /// Summarize the history of the sessions
+    Stats {
+        #[arg(value_enum, default_value_t = stats::View::Today)]
+        view: stats::View,
+        #[arg(long)]
+        /// Start of a custom range, as YYYY-MM-DD
+        from: Option<String>,
+        #[arg(long)]
+        /// End of a custom range, as YYYY-MM-DD, today when missing
+        to: Option<String>,
+        #[arg(long)]
+        /// Print the report as json
+        json: bool,
+    },
+    /// Print the focus blocks of the history for other tools
+    Export {
+        #[arg(short, long, value_enum, default_value_t = export::Format::Csv)]
+        format: export::Format,
+        #[arg(long)]
+        /// Only the blocks started on that day or later, as YYYY-MM-DD
+        since: Option<String>,
+    },
+    /// Keep a list of tasks to work on
+    Task {
+        #[command(subcommand)]
+        command: TaskCommand,
+    },
+    /// Audition the sounds
+    Sounds {
+        #[command(subcommand)]
+        command: SoundsCommand,
+    },
 }
 
-pub fn as_time(seconds: u64) -> String{
-    let left_minutes = if seconds / 60 < 10 {
-        format!("0{}", seconds/60)
-    }else {
-        format!("{}", seconds/60)
-    };
+#[derive(Subcommand, Debug)]
+pub enum TaskCommand {
+    Add {
+        name: String,
+        #[arg(long = "tag")]
+        /// Can be given several times
+        tags: Vec<String>,
+        #[arg(short, long)]
+        /// How many pomodoros it should take
+        estimate: Option<u32>,
+    },
+    /// The tasks with the completed pomodoros over the estimated ones
+    List {
+        #[arg(short, long)]
+        /// The done tasks too
+        all: bool,
+    },
+    Done {
+        id: u32,
+    },
+    Estimate {
+        id: u32,
+        /// How many pomodoros it should take
+        pomodoros: u32,
+    },
+    /// Work on another task for the rest of the running session
+    Switch {
+        /// A free label or the id of a task of the list
+        task: String,
+        #[arg(long = "name", value_parser = parse_timer, default_value = DEFAULT_TIMER)]
+        /// The timer, the one started without `--name` when missing
+        timer: String,
+    },
+}
 
-    let left_seconds = if seconds % 60 < 10 {
-        format!("0{}", seconds % 60)
-    }else {
-        format!("{}", seconds % 60)
-    };
+#[derive(Subcommand, Debug)]
+pub enum SoundsCommand {
+    /// List the sounds compiled into comodo
+    List,
+    /// Play a sound and wait for it to end
+    Preview {
+        /// A builtin sound, with or without the `builtin:` prefix, or a path
+        sound: String,
+    },
+    /// List the audio output devices, the default one is marked with a `*`
+    Devices,
+}
 
-    return format!("{}:{}",left_minutes, left_seconds);
+/// runs `command` against the timer `timer`, whatever goes wrong ends the process
+fn control(timer: String, command: impl FnOnce(&Client) -> Result<(), String>) {
+    if let Err(e) = Client::connect().and_then(|client| command(&client.timer(timer))) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
 
 fn main() {
     let command = Cli::parse(); 
-    let socket_path = "/tmp/comodo.sock";
-    let state_path = "/tmp/state.sock";
+    let socket_path = SOCKET_PATH;
+    let state_path = STATE_PATH;
+    let watch_path = WATCH_PATH;
+    let status_path = STATUS_PATH;
     let daemon_stdout = "/tmp/comodo.out";
     let daemon_stderr = "/tmp/comodo.err";
 
     match command.state {
-        State::Pause => {
-            let mut stream = UnixStream::connect(state_path).unwrap();
-            stream.write_all(&PAUSE).unwrap();
-        },
-        State::Start { focus, rest, number, config } => {
+        State::Pause { timer } => control(timer, Client::pause),
+        State::Start { timer, focus, rest, number, config, task, tags } => {
             let cconfig = if !config.is_empty() {
-                let content = fs::read_to_string(config).unwrap();
-                let config: Config = toml::de::from_str(content.as_str()).unwrap();
-                config.comodo
-            }else {
-                Comodo {
-                    iterations: number,
-                    focus: Duration::from_secs(focus),
-                    rest: Duration::from_secs(rest),
-                    big_rest: Duration::from_secs(900),
-                    popup_notification: true,
-                    sound_notification: false,
-                    focus_notification_banner: String::from("start focusing!"),
-                    rest_notification_banner: String::from("rest steady!"),
-                    focus_audio_notification_path: None,
-                    rest_audio_notification_path: None,
+                let profile = Path::new(&config).file_stem().map(|s| s.to_string_lossy().into_owned());
+                match Config::read(config) {
+                    Ok(mut config) => {
+                        config.comodo.profile = config.comodo.profile.or(profile);
+                        config
+                    },
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    },
                 }
-            };
-
-            let mut stream = UnixStream::connect(socket_path).unwrap();
-            UnixStream::connect(state_path).unwrap();
-
-            let now = SystemTime::now();
-            let since_the_epoch = now.duration_since(UNIX_EPOCH)
-                .expect("Time went backwards");
-            let in_ms = since_the_epoch.as_secs();
-
-            stream.write_all(&DEFAULT).unwrap();
-            stream.write_all(&cconfig.focus.as_secs().to_be_bytes()).unwrap();
-            stream.write_all(&cconfig.rest.as_secs().to_be_bytes()).unwrap();
-            stream.write_all(&cconfig.iterations.to_be_bytes()).unwrap();
-            stream.write_all(&in_ms.to_be_bytes()).unwrap();
-
-            // popup notification flag
-            if cconfig.popup_notification {
-                stream.write_all(&ON).unwrap();
             }else {
-                stream.write_all(&OFF).unwrap();
+                cli_config(number, Duration::from_secs(focus), Duration::from_secs(rest))
+            };
+            let mut cconfig = cconfig;
+            if let Err(e) = with_task(&mut cconfig, task, tags) {
+                eprintln!("{}", e);
+                std::process::exit(1);
             }
 
-            // audio notification flag
-            if cconfig.sound_notification {
-                stream.write_all(&ON).unwrap();
-            }else {
-                stream.write_all(&OFF).unwrap();
+            control(timer, |client| client.start(&cconfig).map_err(|e| e.to_string()));
+        },
+        State::Run { focus, rest, long_rest, number, task, tags } => {
+            let mut config = cli_config(number, focus, rest);
+            config.comodo.big_rest = long_rest;
+            if let Err(e) = with_task(&mut config, task, tags).and_then(|_| foreground::run(&config)) {
+                eprintln!("{}", e);
+                std::process::exit(1);
             }
-            // banners length
-            stream.write_all(&cconfig.focus_notification_banner.len().to_be_bytes()).unwrap();
-            stream.write_all(&cconfig.rest_notification_banner.len().to_be_bytes()).unwrap();
-
-            // path length 
-            stream.write_all(
-                &cconfig.focus_audio_notification_path.clone().unwrap_or(Box::new(PathBuf::new())).to_str().unwrap().len().to_be_bytes()
-                ).unwrap();
-
-            // path length 
-            stream.write_all(
-                &cconfig.rest_audio_notification_path.clone().unwrap_or(Box::new(PathBuf::new())).to_str().unwrap().len().to_be_bytes()
-                ).unwrap();
-
-            stream.write_all(&cconfig.focus_notification_banner.as_bytes()).unwrap();
-            stream.write_all(&cconfig.rest_notification_banner.as_bytes()).unwrap();
-            stream.write_all(
-                &cconfig.focus_audio_notification_path.unwrap_or(Box::new(PathBuf::new())).to_str().unwrap().as_bytes()
-                ).unwrap();
-            stream.write_all(
-                &cconfig.rest_audio_notification_path.unwrap_or(Box::new(PathBuf::new())).to_str().unwrap().as_bytes()
-                ).unwrap();
-            stream.shutdown(std::net::Shutdown::Both).unwrap();
         },
-        State::Stop => {
-            let mut state_stream = UnixStream::connect(state_path).unwrap();
-            state_stream.write_all(&STOP).unwrap();
+        State::Stop { timer } => control(timer, Client::stop),
+        State::Resume { timer } => control(timer, Client::resume),
+        State::Skip { timer } => control(timer, Client::skip),
+        State::Extend { minutes, timer } => control(timer, |client| client.extend(Duration::from_secs(minutes * 60))),
+        State::Interrupt { external, pause, note, timer } => {
+            let mut flags = 0;
+            if external {
+                flags |= INTERRUPT_EXTERNAL;
+            }
+            if pause {
+                flags |= INTERRUPT_PAUSE;
+            }
+            let note = note.unwrap_or_default();
+            control(timer, |client| client.send(&[&INTERRUPT[..], &[flags], note.as_bytes()].concat()));
         },
-        State::Resume => {
+        State::Ack => {
             let mut state_stream = UnixStream::connect(state_path).unwrap();
-            state_stream.write_all(&RESUME).unwrap();
+            state_stream.write_all(&ACK).unwrap();
         },
-        State::Status => {
-            const MAX:u8 = 11;
-            let mut i:u8 = 0;
-
-            let thing = TcpListener::bind("127.0.0.1:8080").unwrap();
-            thing.set_nonblocking(true).unwrap();
-
-            for stream in thing.incoming() {
-                thread::sleep(Duration::from_millis(50));
-                match stream {
-                    Ok(mut stream) => {
-                        let mut body = String::new();
-                        stream.read_to_string(&mut body).unwrap();
-                        println!("{}", body);
-                        break;
-                    }
-                    Err(_) => {
-                        i+=1;
-                    }
-                }
-                if i == MAX {
-                    println!("No pomodoro is running!");
-                    break;
+        State::Watch => {
+            let stream = UnixStream::connect(watch_path).unwrap();
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else { break };
+                match serde_json::from_str::<Message>(&line) {
+                    Ok(message) => println!("{}: {}", message.summary, message.body),
+                    Err(e) => eprintln!("unexpected message from the daemon: {}", e),
                 }
             }
         },
+        State::Status { timer, big: true, follow } => {
+            let timer = timer.unwrap_or(String::from(DEFAULT_TIMER));
+            if let Err(e) = Client::connect().and_then(|client| big::show(&client.timer(timer), follow)) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        State::Status { timer, .. } => {
+            let timers = match Client::connect().and_then(|client| client.timers()) {
+                Ok(timers) => timers,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                },
+            };
+            let running: Vec<_> = timers.iter()
+                .filter(|s| s.running && timer.as_ref().is_none_or(|t| s.timer() == t))
+                .collect();
+            if running.is_empty() {
+                println!("No pomodoro is running!");
+            }
+            for snapshot in running {
+                println!("{}", snapshot.describe());
+            }
+        },
+        State::Tui { timer } => {
+            if let Err(e) = Client::connect().and_then(|client| tui::run(client.timer(timer))) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        State::Prompt { shell } => print!("{}", prompt::snippet(shell, &prompt::path())),
+        State::PromptSegment => {
+            // the daemon keeps the file up to date, a prompt never waits on it
+            let segment = prompt::segment(&prompt::path(), now_in_secs());
+            if !segment.is_empty() {
+                println!("{}", segment);
+            }
+        },
         State::From => todo!(),
+        State::Export { format, since } => {
+            let since = since.map(|d| stats::parse_date(&d)).transpose();
+            let records = history::path()
+                .ok_or(String::from("neither XDG_DATA_HOME nor HOME is set"))
+                .and_then(|path| history::read(&path));
+            match (since, records) {
+                (Ok(since), Ok(records)) => print!("{}", export::export(&records, format, since)),
+                (Err(e), _) | (_, Err(e)) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                },
+            }
+        },
+        State::Task { command } => {
+            let Some(path) = tasks::path() else {
+                eprintln!("neither XDG_DATA_HOME nor HOME is set");
+                std::process::exit(1);
+            };
+            let result = tasks::TaskList::load(&path).and_then(|mut list| {
+                match command {
+                    TaskCommand::Switch { task, timer } => {
+                        let (name, tags) = tasks::resolve(&task)?;
+                        let lines = [name].iter().chain(tags.iter()).cloned().collect::<Vec<_>>().join("\n");
+                        return Client::connect()?.timer(timer).send(&[&TASK[..], lines.as_bytes()].concat());
+                    },
+                    TaskCommand::Add { name, tags, estimate } => {
+                        println!("added task {}", list.add(&name, tags, estimate).id);
+                    },
+                    TaskCommand::List { all } => {
+                        let records = history::path().map(|path| history::read(&path)).unwrap_or(Ok(vec![]))?;
+                        print!("{}", tasks::render(&list, &records, all));
+                        return Ok(());
+                    },
+                    TaskCommand::Done { id } => list.get_mut(id)?.done = true,
+                    TaskCommand::Estimate { id, pomodoros } => list.get_mut(id)?.estimate = Some(pomodoros),
+                }
+                list.save(&path)
+            });
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        State::Stats { view, from, to, json } => {
+            let today = chrono::Local::now().date_naive();
+            let range = match (from, to) {
+                (None, None) => Ok(view.range(today)),
+                (from, to) => {
+                    let from = from.map(|d| stats::parse_date(&d)).unwrap_or(Ok(view.range(today).0));
+                    let to = to.map(|d| stats::parse_date(&d)).unwrap_or(Ok(today));
+                    from.and_then(|from| to.map(|to| (from, to)))
+                },
+            };
+            let records = history::path()
+                .ok_or(String::from("neither XDG_DATA_HOME nor HOME is set"))
+                .and_then(|path| history::read(&path));
+            match (range, records) {
+                (Ok((from, to)), Ok(records)) => {
+                    let report = stats::Report::new(&records, from, to);
+                    if json {
+                        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+                    } else {
+                        print!("{}", stats::render(&report));
+                    }
+                },
+                (Err(e), _) | (_, Err(e)) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                },
+            }
+        },
+        State::Sounds { command: SoundsCommand::List } => {
+            for (name, bytes) in builtin::SOUNDS {
+                println!("{}{}\t{}KB", builtin::PREFIX, name, bytes.len() / 1024);
+            }
+        },
+        State::Sounds { command: SoundsCommand::Devices } => {
+            match player::output_devices() {
+                Ok(devices) => {
+                    for (name, is_default) in devices {
+                        println!("{} {}", if is_default { "*" } else { " " }, name);
+                    }
+                },
+                Err(e) => {
+                    eprintln!("could not list the audio devices: {}", e);
+                    std::process::exit(1);
+                },
+            }
+        },
+        State::Sounds { command: SoundsCommand::Preview { sound } } => {
+            // a bare name is a builtin one unless such a file exists
+            let sound = if !Path::new(&sound).exists() && builtin::get(&sound).is_some() && !sound.starts_with(builtin::PREFIX) {
+                format!("{}{}", builtin::PREFIX, sound)
+            } else {
+                sound
+            };
+            if let Err(e) = player::Player::new(&sound).play() {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
         // TODO: check if daemon is already running
         // TODO: check for necessery file
-        State::Init => {
+        State::Init { restore, discard, config } => {
+            let http = match config.map(Config::read).transpose() {
+                Ok(config) => config.and_then(|c| c.http),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                },
+            };
             if Path::new(socket_path).exists() {
                 println!("Socket file already exist, trying to removing it...");
                 std::fs::remove_file(socket_path).unwrap();
@@ -205,6 +483,58 @@ fn main() {
                 std::fs::remove_file(state_path).unwrap();
                 println!("state file removed!");
             }
+            if Path::new(watch_path).exists() {
+                std::fs::remove_file(watch_path).unwrap();
+            }
+            if Path::new(status_path).exists() {
+                std::fs::remove_file(status_path).unwrap();
+            }
+
+            // asked before daemonizing, while there is still a terminal to ask on
+            let checkpoint = match checkpoint::path().map(|path| (checkpoint::Checkpoints::load(&path), path)) {
+                Some((Ok(Some(checkpoint)), path)) => {
+                    let now = now_in_secs();
+                    let mode = checkpoint.restore_mode();
+                    // `None` when nobody is there to decide, it is left for `comodo init --restore`
+                    let keep = if restore || discard {
+                        Some(restore)
+                    } else {
+                        match mode {
+                            Restore::Auto => Some(true),
+                            Restore::Never => Some(false),
+                            Restore::Ask if std::io::stdin().is_terminal() => {
+                                print!("Restore {}? [Y/n] ", checkpoint.describe(now));
+                                std::io::stdout().flush().unwrap();
+                                let mut answer = String::new();
+                                std::io::stdin().read_line(&mut answer).unwrap_or(0);
+                                Some(!answer.trim().to_lowercase().starts_with('n'))
+                            },
+                            Restore::Ask => {
+                                println!("Found {}, `comodo init --restore` picks it up", checkpoint.describe(now));
+                                None
+                            },
+                        }
+                    };
+                    match keep {
+                        Some(true) => Some(checkpoint),
+                        Some(false) => {
+                            history::remove(&path);
+                            None
+                        },
+                        None => None,
+                    }
+                },
+                Some((Err(e), _)) => {
+                    eprintln!("{}", e);
+                    None
+                },
+                _ => None,
+            };
+
+            // for the bell notifications, the daemon loses its terminal once daemonized
+            let tty = fs::read_link("/proc/self/fd/0").ok()
+                .filter(|path| path.starts_with("/dev/pts") || path.starts_with("/dev/tty"))
+                .and_then(|path| OpenOptions::new().write(true).open(path).ok());
 
             let stdout = File::create(daemon_stdout).unwrap();
             let stderr = File::create(daemon_stderr).unwrap();
@@ -225,271 +555,7 @@ fn main() {
                 Err(e) => eprintln!("Error, {}", e),
             }
 
-            #[allow(unused_assignments)]
-            let mut on_focusing = false;
-            let mut on_focusing_ = false;
-            let socket_stream = UnixListener::bind(socket_path).unwrap();
-            let state_stream = UnixListener::bind(state_path).unwrap();
-
-            state_stream.set_nonblocking(true).unwrap();
-
-            for stream in socket_stream.incoming() {
-                match stream {
-                    Ok(mut stream) => {
-                        let mut _kill_buffer = [0];
-                        let mut _focus_buffer = [0; 8];
-                        let mut _rest_buffer = [0; 8];
-                        let mut _elapsed_buffer = [0; 8];
-                        let mut _number_buffer = [0];
-
-                        let mut pausing = false;
-                        let mut stopping = false;
-
-                        let mut _popup_notification_flag = OFF;
-                        let mut _sound_notification_flag = OFF;
-
-                        let mut _focus_notification_banner_size = [0;8];
-                        let mut _rest_notification_banner_size = [0;8];
-
-                        let mut _focus_notification_path_size = [0;8];
-                        let mut _rest_notification_path_size = [0;8];
-
-                        stream.read(&mut _kill_buffer).unwrap_or(0 as usize);
-                        if _kill_buffer == KILL {
-                            let s = System::new_all();
-                            for process in s.processes_by_name("comodo") {
-                                process.kill();
-                            }
-                            return;
-                        }
-
-                        stream.read(&mut _focus_buffer).unwrap_or(0 as usize);
-                        stream.read(&mut _rest_buffer).unwrap_or(0 as usize);
-                        stream.read(&mut _number_buffer).unwrap_or(0 as usize);
-                        stream.read(&mut _elapsed_buffer).unwrap_or(0 as usize);
-
-                        stream.read(&mut _popup_notification_flag).unwrap_or(0 as usize);
-                        stream.read(&mut _sound_notification_flag).unwrap_or(0 as usize);
-
-                        // focus banner size
-                        let focus_banner_size = if _popup_notification_flag != OFF {
-                            stream.read(&mut _focus_notification_banner_size).unwrap_or(0 as usize);
-                            usize::from_be_bytes(_focus_notification_banner_size)
-                        }else {
-                            0
-                        };
-
-                        // rest banner size
-                        let rest_banner_size = if _popup_notification_flag != OFF {
-                            stream.read(&mut _rest_notification_banner_size).unwrap_or(0 as usize);
-                            usize::from_be_bytes(_rest_notification_banner_size)
-                        }else {
-                            0
-                        };
-
-                        // focus path size
-                        stream.read(&mut _focus_notification_path_size).unwrap_or(0 as usize);
-                        let focus_path_size = usize::from_be_bytes(_focus_notification_path_size);
-
-                        // rest path size
-                        stream.read(&mut _rest_notification_path_size).unwrap_or(0 as usize);
-                        let rest_path_size = usize::from_be_bytes(_rest_notification_path_size);
-
-                        let mut seeker = 0;
-                        let mut sequence = String::new();
-                        stream.read_to_string(&mut sequence).unwrap_or(0 as usize);
-
-                        // focus banner
-                        let focus_banner = if _popup_notification_flag != OFF {
-                            let tmp = sequence[seeker..seeker + focus_banner_size].to_string();
-                            seeker += focus_banner_size;
-                            tmp
-                        }else {
-                            String::new()
-                        };
-
-                        // rest banner
-                        let rest_banner = if _popup_notification_flag != OFF {
-                            let tmp = sequence[seeker..seeker + rest_banner_size].to_string();
-                            seeker += rest_banner_size;
-                            tmp
-                        }else {
-                            String::new()
-                        };
-
-                        // focus path
-                        let focus_path = if focus_path_size != 0 {
-                            let tmp = sequence[seeker..seeker + focus_path_size].to_string();
-                            seeker += focus_path_size;
-                            tmp
-                        } else {
-                            String::new()
-                        };
-
-                        // rest path
-                        let rest_path = if rest_path_size != 0 {
-                            let tmp = sequence[seeker..seeker + rest_path_size].to_string();
-                            seeker += rest_path_size;
-                            tmp
-                        } else {
-                            String::new()
-                        };
-
-                        let focus = u64::from_be_bytes(_focus_buffer);
-                        let rest = u64::from_be_bytes(_rest_buffer);
-                        let elapsed = u64::from_be_bytes(_elapsed_buffer);
-                        let number = u8::from_be_bytes(_number_buffer);
-                        let duty_duration =focus.wrapping_add(rest);
-
-                        let mut now = SystemTime::now();
-                        let mut since_the_epoch = now.duration_since(UNIX_EPOCH)
-                            .expect("Time went backwards");
-                        let mut now_in_secs = since_the_epoch.as_secs();
-
-                        let mut paused_duration = Duration::from_secs(0).as_secs();
-                        let mut _now = SystemTime::now();
-                        let mut __now = SystemTime::now();
-
-                        let mut time_since_started = now_in_secs - elapsed;
-                        let mut n = time_since_started / duty_duration;
-
-                        while n < number as u64 {
-                            thread::sleep(Duration::from_millis(500));
-                            // what am I trying to calculate
-                            // 1) did we finished our duty?
-                            // 2) what is the current countdown time?
-                            // 3) on what state are we (focusing vs rest)
-
-                            for action_buffer in state_stream.incoming() {
-                                match action_buffer {
-                                    Ok(mut stream) => {
-                                        let mut action = DEFAULT;
-                                        stream.read(&mut action).unwrap();
-
-                                        // checking for signals
-                                        if action == PAUSE {
-                                            _now = SystemTime::now();
-                                            pausing = true;
-                                            println!("pause recived");
-                                        }else if action == RESUME {
-                                            __now = SystemTime::now();
-                                            paused_duration = paused_duration
-                                                .checked_add(
-                                                    __now.duration_since(_now)
-                                                    .unwrap_or(Duration::ZERO)
-                                                    .as_secs()
-                                                    ).unwrap_or(0);
-                                            pausing = false;
-                                        }else if action == STOP {
-                                            stopping = true;
-                                        }
-                                        break;
-                                    },
-                                    Err(_) => { break; },
-                                }
-                            }
-
-                            let t = time_since_started % duty_duration;
-                            let focusing = focus.checked_sub(t).unwrap_or(0) > 0;
-                            let focusing_duration = if focusing {
-                                t
-                            }else {
-                                0
-                            };
-                            let rest_duration= t.checked_sub(focus).unwrap_or(0);
-
-                            if pausing {
-                                if let Ok(mut stream) = TcpStream::connect("127.0.0.1:8080") {
-                                    stream.write_all(
-                                        format!("stateus: Pause\r\niteration: {}/{}\r\nfocus: {}/{}\r\nrest: {}/{}\r\n",
-                                                number, n,
-                                                as_time(focusing_duration), as_time(focus),
-                                                as_time(rest_duration), as_time(rest)).as_bytes()).unwrap();
-                                }
-
-                                continue;
-                            } else if stopping{
-                                break;
-                            }
-
-
-                            if focusing {
-                                if let Ok(mut stream) = TcpStream::connect("127.0.0.1:8080") {
-                                    stream.write_all(
-                                        format!("stateus: focusing\r\niteration: {}/{}\r\nfocus: {}/{}\r\nrest: {}/{}\r\n",
-                                                number, n,
-                                                as_time(focusing_duration), as_time(focus),
-                                                as_time(rest_duration), as_time(rest)).as_bytes()).unwrap();
-                                }
-
-                                on_focusing = true;
-
-                                if on_focusing != on_focusing_ {
-                                    if _popup_notification_flag != OFF {
-                                        Notification::new()
-                                            .summary("Comodo Pomodoro")
-                                            .body(&focus_banner.replace("#", &format!("{}", n+1)))
-                                            .appname("comodo")
-                                            .timeout(Timeout::from(Duration::from_secs(2)))
-                                            .show().unwrap();
-                                    }
-                                }
-                                on_focusing_ = true;
-                            } else {
-                                if let Ok(mut stream) = TcpStream::connect("127.0.0.1:8080") {
-                                    println!("Connected to the server!");
-                                    stream.write_all(
-                                        format!("stateus: resting\r\niteration: {}/{}\r\nfocus: {}/{}\r\nrest: {}/{}\r\n",
-                                                number, n,
-                                                as_time(focusing_duration), as_time(focus),
-                                                as_time(rest_duration), as_time(rest)).as_bytes()).unwrap();
-                                }
-
-                                on_focusing = false;
-
-                                if on_focusing != on_focusing_ {
-                                    if _popup_notification_flag != OFF {
-                                        Notification::new()
-                                            .summary("Comodo Pomodoro")
-                                            .body(&rest_banner.replace("#", &format!("{}", n + 1)))
-                                            .appname("comodo")
-                                            .timeout(Timeout::from(Duration::from_secs(2)))
-                                            .show().unwrap();
-                                    }
-                                    if _sound_notification_flag != OFF {
-                                        let player = player::Player::new(&focus_path);
-                                        player.play();
-                                    }
-                                }
-                                on_focusing_ = false;
-                            }
-
-                            now = SystemTime::now();
-                            since_the_epoch = now.duration_since(UNIX_EPOCH)
-                                .expect("Time went backwards");
-                            now_in_secs = since_the_epoch.as_secs();
-
-                            time_since_started = now_in_secs - elapsed - paused_duration;
-                            n = time_since_started / duty_duration;
-                        }
-
-                        if _popup_notification_flag != OFF {
-                            Notification::new()
-                                .summary("Comodo Pomodoro")
-                                .body(&format!("End of Session!"))
-                                .appname("comodo")
-                                .timeout(Timeout::from(Duration::from_secs(2)))
-                                .show().unwrap();
-                        }
-                        if _sound_notification_flag != OFF {
-                            let player = player::Player::new(&rest_path);
-                            player.play();
-                        }
-                    },
-                    Err(_) => {
-                    },
-                }
-            }
+            daemon::run(socket_path, state_path, watch_path, status_path, tty, checkpoint, http);
         },
         State::Kill => {
             let mut stream = UnixStream::connect(socket_path).unwrap();
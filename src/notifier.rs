@@ -0,0 +1,474 @@
+use std::{
+    cell::RefCell, fs::{File, OpenOptions}, io::Write, os::unix::{fs::OpenOptionsExt, net::UnixStream},
+    path::PathBuf, rc::Rc, thread, time::Duration,
+};
+
+use notify_rust::{ActionResponse, Hint, Notification, NotificationHandle, Timeout, Urgency as DesktopUrgency};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{Backend, Notifications, Popup, Urgency},
+    as_time,
+    protocol::{to_timer, EXTEND, PAUSE, SKIP},
+    session::{Phase, Status},
+};
+
+/// A button offered along with a notification, pressing it controls the running session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Pause,
+    Skip,
+    /// pushes the end of the current phase back by 5 minutes
+    Extend,
+}
+
+impl Action {
+    pub const EXTEND_BY: Duration = Duration::from_secs(5 * 60);
+
+    /// the buttons offered when `phase` starts
+    pub fn for_phase(phase: Phase) -> Vec<Action> {
+        match phase {
+            Phase::Focus => vec![Action::Pause, Action::Skip],
+            Phase::ShortBreak | Phase::LongBreak => vec![Action::Skip, Action::Extend],
+        }
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Action::Pause => "pause",
+            Action::Skip => "skip",
+            Action::Extend => "extend",
+        }
+    }
+
+    pub fn label(&self, phase: Option<Phase>) -> &'static str {
+        match (self, phase) {
+            (Action::Pause, _) => "Pause",
+            (Action::Skip, Some(Phase::ShortBreak | Phase::LongBreak)) => "Skip break",
+            (Action::Skip, _) => "Skip",
+            (Action::Extend, _) => "+5 min",
+        }
+    }
+
+    /// what gets written to the state socket, as `comodo pause/skip/extend` would
+    pub fn command(&self) -> Vec<u8> {
+        match self {
+            Action::Pause => PAUSE.to_vec(),
+            Action::Skip => SKIP.to_vec(),
+            Action::Extend => [&EXTEND[..], &Action::EXTEND_BY.as_secs().to_be_bytes()].concat(),
+        }
+    }
+}
+
+/// What every backend gets to show
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Message {
+    pub summary: String,
+    pub body: String,
+    /// the phase that just started, `None` once the session is over
+    #[serde(default)]
+    pub phase: Option<Phase>,
+    #[serde(default)]
+    pub actions: Vec<Action>,
+}
+
+pub trait Notifier {
+    fn name(&self) -> &'static str;
+    fn notify(&mut self, message: &Message) -> Result<(), String>;
+}
+
+/// Popups through the desktop notification daemon
+pub struct Desktop {
+    notifications: Notifications,
+    /// where the pressed buttons are sent to
+    state_path: PathBuf,
+    timer: String,
+    /// the last popup with buttons, waited on until it is closed
+    shown: Option<NotificationHandle>,
+}
+
+impl Desktop {
+    fn notification(popup: &Popup, message: &Message) -> Notification {
+        let mut notification = Notification::new();
+        notification
+            .summary(&message.summary)
+            .body(&message.body)
+            .appname("comodo")
+            .urgency(match popup.urgency {
+                Urgency::Low => DesktopUrgency::Low,
+                Urgency::Normal => DesktopUrgency::Normal,
+                Urgency::Critical => DesktopUrgency::Critical,
+            })
+            .timeout(match popup.timeout {
+                0 => Timeout::Never,
+                seconds => Timeout::from(Duration::from_secs(seconds as u64)),
+            });
+        if let Some(icon) = &popup.icon {
+            notification.icon(icon);
+        }
+        if popup.actions {
+            for action in &message.actions {
+                notification.action(action.id(), action.label(message.phase));
+            }
+        }
+        notification
+    }
+}
+
+impl Notifier for Desktop {
+    fn name(&self) -> &'static str { "desktop" }
+
+    fn notify(&mut self, message: &Message) -> Result<(), String> {
+        // the buttons of the previous phase are stale, closing the popup
+        // also ends the thread waiting on it so they never pile up
+        if let Some(previous) = self.shown.take() {
+            previous.close();
+        }
+        let popup = self.notifications.popup(message.phase);
+        let handle = Desktop::notification(popup, message).show().map_err(|e| e.to_string())?;
+        if !popup.actions || message.actions.is_empty() {
+            return Ok(());
+        }
+
+        // waiting for a button blocks until the popup is gone, the daemon can not afford that
+        let id = handle.id();
+        let actions = message.actions.clone();
+        let state_path = self.state_path.clone();
+        let timer = self.timer.clone();
+        thread::spawn(move || {
+            notify_rust::handle_action(id, |response: &ActionResponse| {
+                let ActionResponse::Custom(id) = response else { return };
+                let Some(action) = actions.iter().find(|a| a.id() == *id) else { return };
+                let sent = UnixStream::connect(&state_path).and_then(|mut stream| stream.write_all(&to_timer(&timer, &action.command())));
+                if let Err(e) = sent {
+                    eprintln!("could not send the {} action: {}", id, e);
+                }
+            });
+        });
+        self.shown = Some(handle);
+        Ok(())
+    }
+}
+
+/// A single desktop popup kept open for the whole session and updated in place
+/// with the time left, rather than a popup per phase
+pub struct Countdown {
+    handle: Option<NotificationHandle>,
+    /// seconds between two updates
+    interval: u64,
+    /// when it was last shown and for what, a new phase or a pause gets shown right away
+    last: Option<(u64, Phase, u64, bool)>,
+    task: Option<String>,
+}
+
+impl Countdown {
+    /// `None` unless the desktop backend is used and the countdown is turned on
+    pub fn new(notifications: &Notifications, task: Option<String>) -> Option<Self> {
+        let desktop = notifications.backends.contains(&Backend::Desktop)
+            && !notifications.backends.contains(&Backend::None);
+        (desktop && notifications.countdown > 0).then(|| Countdown {
+            handle: None,
+            interval: notifications.countdown as u64,
+            last: None,
+            task,
+        })
+    }
+
+    pub fn text(&self, status: &Status) -> (String, String) {
+        let left = status.length.saturating_sub(status.elapsed).as_secs();
+        let summary = format!("comodo: {} {}/{}", status.phase.name(), status.cycle, status.total);
+        let mut body = if status.paused {
+            format!("paused, {} left", as_time(left))
+        } else {
+            format!("{} left", as_time(left))
+        };
+        if let Some(task) = &self.task {
+            body += &format!("\n{}", task);
+        }
+        (summary, body)
+    }
+
+    fn due(&self, status: &Status, now: u64) -> bool {
+        match self.last {
+            None => true,
+            Some((at, phase, cycle, paused)) => {
+                (phase, cycle, paused) != (status.phase, status.cycle, status.paused)
+                    || now.saturating_sub(at) >= self.interval
+            },
+        }
+    }
+
+    pub fn update(&mut self, status: &Status, now: u64) -> Result<(), String> {
+        if !self.due(status, now) {
+            return Ok(());
+        }
+        self.last = Some((now, status.phase, status.cycle, status.paused));
+
+        let (summary, body) = self.text(status);
+        let progress = status.elapsed.as_secs() * 100 / status.length.as_secs().max(1);
+        match &mut self.handle {
+            Some(handle) => {
+                // the same id, the notification daemon replaces the popup instead of stacking a new one
+                handle.summary(&summary)
+                    .body(&body)
+                    .hint(Hint::CustomInt(String::from("value"), progress as i32));
+                handle.update();
+            },
+            None => {
+                let handle = Notification::new()
+                    .summary(&summary)
+                    .body(&body)
+                    .appname("comodo")
+                    .hint(Hint::Resident(true))
+                    .hint(Hint::CustomInt(String::from("value"), progress as i32))
+                    .timeout(Timeout::Never)
+                    .show()
+                    .map_err(|e| e.to_string())?;
+                self.handle = Some(handle);
+            },
+        }
+        Ok(())
+    }
+
+    pub fn set_task(&mut self, task: Option<String>) {
+        self.task = task;
+        // shown at the next update
+        self.last = None;
+    }
+
+    /// once the session is over or stopped
+    pub fn close(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.close();
+        }
+    }
+}
+
+/// Rings the terminal `comodo init` was started from
+pub struct Bell {
+    tty: Option<Rc<File>>,
+}
+
+impl Notifier for Bell {
+    fn name(&self) -> &'static str { "bell" }
+
+    fn notify(&mut self, _message: &Message) -> Result<(), String> {
+        let mut tty = self.tty.as_deref().ok_or("the daemon was not started from a terminal")?;
+        tty.write_all(b"\x07").map_err(|e| e.to_string())
+    }
+}
+
+/// Appends a `summary: body` line to a file or a FIFO
+pub struct FileNotifier {
+    path: std::path::PathBuf,
+}
+
+impl Notifier for FileNotifier {
+    fn name(&self) -> &'static str { "file" }
+
+    fn notify(&mut self, message: &Message) -> Result<(), String> {
+        // non blocking so a FIFO nobody reads from fails right away instead of hanging the daemon
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&self.path)
+            .map_err(|e| format!("could not open {}: {}", self.path.display(), e))?;
+        writeln!(file, "{}: {}", message.summary, message.body).map_err(|e| e.to_string())
+    }
+}
+
+/// The clients attached with `comodo watch`, each one gets a json line per message
+#[derive(Debug, Default, Clone)]
+pub struct Clients {
+    streams: Rc<RefCell<Vec<UnixStream>>>,
+}
+
+impl Clients {
+    /// a client that stops reading must not stall the daemon, writing to
+    /// it fails once its buffer is full instead
+    pub fn attach(&self, stream: UnixStream) {
+        match stream.set_nonblocking(true) {
+            Ok(()) => self.streams.borrow_mut().push(stream),
+            Err(e) => eprintln!("could not attach a client: {}", e),
+        }
+    }
+
+    /// sends `line` to every client, dropping the ones that went away or fell behind
+    pub fn broadcast(&self, line: &str) {
+        let line = format!("{}\n", line);
+        self.streams.borrow_mut().retain_mut(|stream| stream.write_all(line.as_bytes()).is_ok());
+    }
+}
+
+impl Notifier for Clients {
+    fn name(&self) -> &'static str { "clients" }
+
+    fn notify(&mut self, message: &Message) -> Result<(), String> {
+        self.broadcast(&serde_json::to_string(message).map_err(|e| e.to_string())?);
+        Ok(())
+    }
+}
+
+/// What the daemon holds for the whole of its life and the backends need
+#[derive(Debug, Default, Clone)]
+pub struct Context {
+    pub tty: Option<Rc<File>>,
+    pub clients: Clients,
+    pub state_path: PathBuf,
+}
+
+/// the backends chosen in the config for the timer `timer`, `none` wins over
+/// everything else
+pub fn build(notifications: &Notifications, context: &Context, timer: &str) -> Vec<Box<dyn Notifier>> {
+    if notifications.backends.contains(&Backend::None) {
+        return vec![];
+    }
+    notifications.backends.iter().map(|backend| -> Box<dyn Notifier> {
+        match backend {
+            Backend::Desktop => Box::new(Desktop {
+                notifications: notifications.clone(),
+                state_path: context.state_path.clone(),
+                timer: timer.to_string(),
+                shown: None,
+            }),
+            Backend::Bell => Box::new(Bell { tty: context.tty.clone() }),
+            // validated while deserializing, the file backend comes with a path
+            Backend::File => Box::new(FileNotifier { path: notifications.file.clone().unwrap_or_default() }),
+            Backend::Clients => Box::new(context.clients.clone()),
+            Backend::None => unreachable!(),
+        }
+    }).collect()
+}
+
+/// a failing backend is logged and does not stop the other ones
+pub fn notify_all(notifiers: &mut [Box<dyn Notifier>], message: &Message) {
+    for notifier in notifiers {
+        if let Err(e) = notifier.notify(message) {
+            eprintln!("{} notification failed: {}", notifier.name(), e);
+        }
+    }
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use std::{fs, io::{BufRead, BufReader}, os::unix::net::UnixStream};
+    #[allow(unused_imports)]
+    use std::time::Duration;
+    #[allow(unused_imports)]
+    use crate::{config::{Backend, Notifications}, protocol::{DEFAULT_TIMER, EXTEND, PAUSE, SKIP}, session::{Phase, Status}};
+    #[allow(unused_imports)]
+    use super::{build, notify_all, Action, Context, Countdown, Message};
+
+    #[allow(dead_code)]
+    fn message() -> Message {
+        Message {
+            summary: String::from("Comodo Pomodoro"),
+            body: String::from("rest steady!"),
+            phase: Some(Phase::ShortBreak),
+            actions: Action::for_phase(Phase::ShortBreak),
+        }
+    }
+
+    #[test]
+    fn file_test() {
+        let path = std::env::temp_dir().join(format!("comodo-notifier-{}", std::process::id()));
+        let notifications = Notifications {
+            backends: vec![Backend::File, Backend::Bell],
+            file: Some(path.clone()),
+            ..Default::default()
+        };
+        let mut notifiers = build(&notifications, &Context::default(), DEFAULT_TIMER);
+        assert_eq!(notifiers.iter().map(|n| n.name()).collect::<Vec<_>>(), vec!["file", "bell"]);
+
+        // the bell has no terminal to ring, that should not keep the file from being written
+        notify_all(&mut notifiers, &message());
+        notify_all(&mut notifiers, &message());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Comodo Pomodoro: rest steady!\nComodo Pomodoro: rest steady!\n");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn clients_test() {
+        let context = Context::default();
+        let (daemon, client) = UnixStream::pair().unwrap();
+        context.clients.attach(daemon);
+
+        let notifications = Notifications { backends: vec![Backend::Clients], ..Default::default() };
+        notify_all(&mut build(&notifications, &context, DEFAULT_TIMER), &message());
+
+        let mut line = String::new();
+        BufReader::new(client).read_line(&mut line).unwrap();
+        assert_eq!(serde_json::from_str::<Message>(&line).unwrap(), message());
+
+        // one that never reads gets dropped rather than blocking the daemon
+        let (daemon, _client) = UnixStream::pair().unwrap();
+        context.clients.attach(daemon);
+        let line = "x".repeat(4096);
+        for _ in 0..1024 {
+            context.clients.broadcast(&line);
+        }
+        assert!(context.clients.streams.borrow().is_empty());
+    }
+
+    #[test]
+    fn none_test() {
+        let notifications = Notifications { backends: vec![Backend::Desktop, Backend::None], ..Default::default() };
+        assert!(build(&notifications, &Context::default(), DEFAULT_TIMER).is_empty());
+    }
+
+    #[test]
+    fn action_test() {
+        assert_eq!(Action::for_phase(Phase::Focus), vec![Action::Pause, Action::Skip]);
+        assert_eq!(Action::for_phase(Phase::LongBreak), vec![Action::Skip, Action::Extend]);
+        assert_eq!(Action::Skip.label(Some(Phase::Focus)), "Skip");
+        assert_eq!(Action::Skip.label(Some(Phase::ShortBreak)), "Skip break");
+        assert_eq!(Action::Extend.label(Some(Phase::LongBreak)), "+5 min");
+
+        assert_eq!(Action::Pause.command(), PAUSE.to_vec());
+        assert_eq!(Action::Skip.command(), SKIP.to_vec());
+        let extend = Action::Extend.command();
+        assert_eq!(extend[..1], EXTEND);
+        assert_eq!(u64::from_be_bytes(extend[1..].try_into().unwrap()), 300);
+
+        // older clients sent messages without the actions
+        let message: Message = serde_json::from_str(r#"{"summary":"a","body":"b"}"#).unwrap();
+        assert_eq!((message.phase, message.actions), (None, vec![]));
+    }
+
+    #[test]
+    fn countdown_test() {
+        let off = Notifications::default();
+        assert!(Countdown::new(&off, None).is_none());
+        let clients = Notifications { backends: vec![Backend::Clients], countdown: 5, ..Default::default() };
+        assert!(Countdown::new(&clients, None).is_none());
+
+        let mut status = Status {
+            phase: Phase::Focus,
+            cycle: 2,
+            total: 4,
+            elapsed: Duration::from_secs(60),
+            length: Duration::from_secs(25 * 60),
+            paused: false,
+        };
+        let on = Notifications { countdown: 5, ..Default::default() };
+        let mut countdown = Countdown::new(&on, None).unwrap();
+        assert_eq!(countdown.text(&status), (String::from("comodo: focus 2/4"), String::from("24:00 left")));
+        let task = Countdown::new(&on, Some(String::from("review PR 42"))).unwrap();
+        assert_eq!(task.text(&status).1, "24:00 left\nreview PR 42");
+
+        assert!(countdown.due(&status, 100));
+        countdown.last = Some((100, Phase::Focus, 2, false));
+        assert!(!countdown.due(&status, 104));
+        assert!(countdown.due(&status, 105));
+
+        // pausing or moving on to the next phase shows up right away
+        status.paused = true;
+        assert!(countdown.due(&status, 101));
+        assert_eq!(countdown.text(&status).1, "paused, 24:00 left");
+        status.paused = false;
+        status.phase = Phase::ShortBreak;
+        assert!(countdown.due(&status, 101));
+    }
+}
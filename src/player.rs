@@ -1,12 +1,26 @@
-use std::{path, io::BufReader, time::Duration};
+use std::{
+    path, time::Duration,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender}, thread,
+};
 
-use rodio::Source;
+use rodio::{
+    buffer::SamplesBuffer, cpal::{self, traits::HostTrait}, Decoder, Device, DeviceTrait,
+    OutputStream, OutputStreamHandle, Sink, Source,
+};
+
+use crate::{ambient::{Noise, Tick}, builtin::{self, Data}, config::{Background, BackgroundSource, Sound}};
 
 #[derive(Debug)]
 pub struct Player<'a> {
     file_path: &'a path::Path
 }
 
+/// `file_path` can also be one of the `builtin:` sounds
+fn decode(file_path: &path::Path) -> Result<Decoder<Data>, String> {
+    Decoder::new(builtin::open(file_path)?)
+        .map_err(|e| format!("could not decode {}: {}", file_path.display(), e))
+}
+
 impl<'a> Player<'a> {
     pub fn new(file_path: &'a str) -> Self{
         Player {
@@ -14,40 +28,308 @@ impl<'a> Player<'a> {
         }
     }
 
-    #[allow(unused)]
-    pub fn play(&self) {
-        let (_stream, handle) = rodio::OutputStream::try_default().unwrap();
-        let sink = rodio::Sink::try_new(&handle).unwrap();
+    /// plays the file on the calling thread, blocking until it ends
+    pub fn play(&self) -> Result<(), String> {
+        let (_stream, handle) = OutputStream::try_default().map_err(|e| e.to_string())?;
+        let sink = Sink::try_new(&handle).map_err(|e| e.to_string())?;
 
-        let file = std::fs::File::open(self.file_path.to_str().unwrap()).unwrap();
-        let source = rodio::Decoder::new(BufReader::new(file)).unwrap();
-        sink.append(source);
+        sink.append(decode(self.file_path)?);
 
         sink.sleep_until_end();
         sink.detach();
+        Ok(())
     }
 
-    pub fn play_until(&self, duration: Duration) {
-        let (_stream, handle) = rodio::OutputStream::try_default().unwrap();
-        let sink = rodio::Sink::try_new(&handle).unwrap();
+    #[allow(unused)]
+    pub fn play_until(&self, duration: Duration) -> Result<(), String> {
+        let (_stream, handle) = OutputStream::try_default().map_err(|e| e.to_string())?;
+        let sink = Sink::try_new(&handle).map_err(|e| e.to_string())?;
 
-        let file = std::fs::File::open(self.file_path.to_str().unwrap()).unwrap();
-        let source = rodio::Decoder::new(BufReader::new(file)).unwrap()
+        let source = decode(self.file_path)?
             .stoppable()
             .take_duration(duration);
 
         sink.append(source);
 
         sink.sleep_until_end();
+        Ok(())
+    }
+}
+
+/// A decoded sound with its `max_duration` and fades already applied
+#[derive(Debug, Clone)]
+struct Clip {
+    channels: u16,
+    sample_rate: u32,
+    samples: Vec<f32>,
+}
+
+impl Clip {
+    fn load(sound: &Sound) -> Result<Self, String> {
+        let source = decode(&sound.path)?;
+        let channels = source.channels();
+        let sample_rate = source.sample_rate();
+        let frame_rate = sample_rate as f32 * channels as f32;
+
+        let samples: Vec<f32> = match sound.max_duration {
+            Some(max) => source.convert_samples().take((max.as_secs_f32() * frame_rate) as usize).collect(),
+            None => source.convert_samples().collect(),
+        };
+        let mut clip = Clip { channels, sample_rate, samples };
+
+        let fade_in = (sound.fade_in.as_secs_f32() * sample_rate as f32) as usize;
+        let fade_out = (sound.fade_out.as_secs_f32() * sample_rate as f32) as usize;
+        // the gain `frames` into a ramp `length` frames long, no ramp leaves it alone
+        let ramp = |frames: usize, length: usize| if length == 0 { 1. } else { (frames as f32 / length as f32).min(1.) };
+        let frames = clip.samples.len() / channels as usize;
+        for (i, frame) in clip.samples.chunks_mut(channels as usize).enumerate() {
+            let gain = ramp(i, fade_in).min(ramp(frames.saturating_sub(i + 1), fade_out));
+            frame.iter_mut().for_each(|sample| *sample *= gain);
+        }
+        Ok(clip)
+    }
+
+    fn source(&self) -> SamplesBuffer<f32> {
+        SamplesBuffer::new(self.channels, self.sample_rate, self.samples.clone())
+    }
+}
+
+enum Request {
+    Play(Sound),
+    Acknowledge,
+    /// replaces the current background track, `None` stops it
+    Background { background: Option<Background>, ducking: u16 },
+    PauseBackground,
+    ResumeBackground,
+    /// name or part of the name of the output device, `None` for the default one
+    Device(Option<String>),
+}
+
+/// Handle to the audio thread, the thread owns the output stream for the
+/// whole life of the daemon and plays whatever it is sent without ever
+/// making the sender wait
+#[derive(Debug, Clone)]
+pub struct AudioService {
+    sender: Sender<Request>,
+}
+
+impl AudioService {
+    /// must be called after daemonizing, the thread would not survive the fork
+    pub fn spawn() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::Builder::new()
+            .name(String::from("audio"))
+            .spawn(move || run(receiver))
+            .unwrap();
+        AudioService { sender }
+    }
+
+    pub fn play(&self, sound: &Sound) {
+        if self.sender.send(Request::Play(sound.clone())).is_err() {
+            eprintln!("audio thread is gone, not playing {}", sound.path.display());
+        }
+    }
+
+    /// stop the sounds that repeat until acknowledged
+    pub fn acknowledge(&self) {
+        self.sender.send(Request::Acknowledge).ok();
+    }
+
+    /// `ducking` is the percent of its volume the track keeps while another sound plays
+    pub fn background(&self, background: Option<&Background>, ducking: u16) {
+        self.sender.send(Request::Background { background: background.cloned(), ducking }).ok();
+    }
+
+    pub fn pause_background(&self) {
+        self.sender.send(Request::PauseBackground).ok();
+    }
+
+    pub fn resume_background(&self) {
+        self.sender.send(Request::ResumeBackground).ok();
+    }
+
+    /// switching device cuts what is playing, the background track starts over on the new one
+    pub fn device(&self, device: Option<&str>) {
+        self.sender.send(Request::Device(device.map(String::from))).ok();
+    }
+}
+
+/// the name of every output device and whether it is the default one
+pub fn output_devices() -> Result<Vec<(String, bool)>, String> {
+    let host = cpal::default_host();
+    let default = host.default_output_device().and_then(|d| d.name().ok());
+    let devices = host.output_devices().map_err(|e| e.to_string())?;
+    Ok(devices
+        .filter_map(|d| d.name().ok())
+        .map(|name| {
+            let is_default = default.as_ref() == Some(&name);
+            (name, is_default)
+        })
+        .collect())
+}
+
+// an exact match wins over a case insensitive substring one
+fn find_device(name: &str) -> Option<Device> {
+    let mut devices: Vec<(String, Device)> = cpal::default_host()
+        .output_devices().ok()?
+        .filter_map(|d| d.name().ok().map(|n| (n, d)))
+        .collect();
+    let lowercase = name.to_lowercase();
+    let index = devices.iter().position(|(n, _)| n == name)
+        .or_else(|| devices.iter().position(|(n, _)| n.to_lowercase().contains(&lowercase)))?;
+    Some(devices.swap_remove(index).1)
+}
+
+struct Output {
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+}
+
+// a configured device that is missing falls back on the default one, rodio
+// itself falls back on the first working device when the default one can't be
+// opened, when there is none at all the sounds are skipped and the next request
+// tries again in case a device showed up in between
+fn open_output(device: Option<&str>) -> Option<Output> {
+    if let Some(name) = device {
+        match find_device(name).map(|d| OutputStream::try_from_device(&d)) {
+            Some(Ok((_stream, handle))) => return Some(Output { _stream, handle }),
+            Some(Err(e)) => eprintln!("could not open audio device {:?}, using the default one: {}", name, e),
+            None => eprintln!("no audio device matches {:?}, using the default one", name),
+        }
+    }
+    match OutputStream::try_default() {
+        Ok((_stream, handle)) => Some(Output { _stream, handle }),
+        Err(e) => {
+            eprintln!("no audio output available, skipping sounds: {}", e);
+            None
+        },
+    }
+}
+
+fn start(output: &Output, sound: &Sound) -> Result<Sink, String> {
+    let clip = Clip::load(sound)?;
+    let sink = Sink::try_new(&output.handle).map_err(|e| e.to_string())?;
+    sink.set_volume(sound.volume as f32 / 100.);
+    if sound.until_ack {
+        sink.append(clip.source().repeat_infinite());
+    } else {
+        for _ in 0..sound.repeat {
+            sink.append(clip.source());
+        }
+    }
+    Ok(sink)
+}
+
+fn start_background(output: &Output, background: &Background) -> Result<Sink, String> {
+    let sink = Sink::try_new(&output.handle).map_err(|e| e.to_string())?;
+    match &background.source {
+        BackgroundSource::File(file_path) => {
+            let source = Decoder::new_looped(builtin::open(file_path)?)
+                .map_err(|e| format!("could not decode {}: {}", file_path.display(), e))?;
+            sink.append(source);
+        },
+        BackgroundSource::Tick => sink.append(Tick::new()),
+        BackgroundSource::Noise(color) => sink.append(Noise::new(*color)),
+    }
+    Ok(sink)
+}
+
+fn resume_playing(output: &Option<Output>, playing: &Option<(Background, u16)>) -> Option<(Sink, f32, f32)> {
+    let (Some(output), Some((next, ducking))) = (output, playing) else {
+        return None;
+    };
+    match start_background(output, next) {
+        Ok(sink) => Some((sink, next.volume as f32 / 100., *ducking as f32 / 100.)),
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        },
+    }
+}
+
+fn run(receiver: Receiver<Request>) {
+    let mut device: Option<String> = None;
+    let mut output = open_output(None);
+    // kept around so the sounds are not cut when their sink is dropped,
+    // the flag tells whether it waits for an acknowledgement
+    let mut sinks: Vec<(Sink, bool)> = vec![];
+    // the track, its volume and how much of it is kept while ducked
+    let mut background: Option<(Sink, f32, f32)> = None;
+    // what the track is playing, to start it over on another device
+    let mut playing: Option<(Background, u16)> = None;
+
+    loop {
+        // short enough for the ducking to follow the notification sounds
+        match receiver.recv_timeout(Duration::from_millis(100)) {
+            Ok(Request::Background { background: next, ducking }) => {
+                if let Some((sink, _, _)) = background.take() {
+                    sink.stop();
+                }
+                playing = next.map(|next| (next, ducking));
+                if output.is_none() && playing.is_some() {
+                    output = open_output(device.as_deref());
+                }
+                background = resume_playing(&output, &playing);
+            },
+            Ok(Request::Device(next)) => {
+                if next != device {
+                    device = next;
+                    let paused = background.as_ref().is_some_and(|(sink, _, _)| sink.is_paused());
+                    sinks.clear();
+                    background.take();
+                    output = open_output(device.as_deref());
+                    background = resume_playing(&output, &playing);
+                    if let (Some((sink, _, _)), true) = (&background, paused) {
+                        sink.pause();
+                    }
+                }
+            },
+            Ok(Request::PauseBackground) => {
+                if let Some((sink, _, _)) = &background {
+                    sink.pause();
+                }
+            },
+            Ok(Request::ResumeBackground) => {
+                if let Some((sink, _, _)) = &background {
+                    sink.play();
+                }
+            },
+            Ok(Request::Play(sound)) => {
+                if output.is_none() {
+                    output = open_output(device.as_deref());
+                }
+                if let Some(output) = &output {
+                    match start(output, &sound) {
+                        Ok(sink) => sinks.push((sink, sound.until_ack)),
+                        Err(e) => eprintln!("{}", e),
+                    }
+                }
+            },
+            Ok(Request::Acknowledge) => {
+                for (sink, _) in sinks.iter().filter(|(_, until_ack)| *until_ack) {
+                    sink.stop();
+                }
+            },
+            Err(RecvTimeoutError::Timeout) => {},
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        sinks.retain(|(sink, _)| !sink.empty());
+
+        if let Some((sink, volume, ducking)) = &background {
+            let ducked = if sinks.is_empty() { 1. } else { *ducking };
+            sink.set_volume(volume * ducked);
+        }
     }
 }
 
 mod test{
     #[allow(unused_imports)]
-    use std::time::Duration;
+    use std::{path::Path, time::Duration};
+    #[allow(unused_imports)]
+    use rodio::Source;
     #[allow(unused_imports)]
-    use crate::player::Player;
-    
+    use crate::{config::Sound, player::{decode, Clip, Player}};
+
     #[test]
     fn play_test() {
         // let path = String::from("assets/hotel-bell-ding-1-174457.mp3");
@@ -57,9 +339,38 @@ mod test{
     }
 
     #[test]
+    #[ignore = "plays on the default output device"]
     fn play_until_test() {
-        let path = String::from("./assets/hotel-bell-ding-1-174457.mp3");
+        let path = String::from("builtin:bell");
         let player = Player::new(&path);
-        player.play_until(Duration::from_secs(2));
+        player.play_until(Duration::from_secs(2)).unwrap();
+    }
+
+    #[test]
+    fn decode_test() {
+        assert!(decode(Path::new("./assets/hotel-bell-ding-1-174457.mp3")).is_ok());
+        assert!(decode(Path::new("builtin:doorbell")).is_ok());
+        assert!(decode(Path::new("./assets/missing.mp3")).is_err_and(|e| e.contains("could not open")));
+    }
+
+    #[test]
+    fn clip_test() {
+        let mut sound = Sound::new(Box::new(Path::new("./assets/hotel-bell-ding-1-174457.mp3").to_owned()));
+        sound.max_duration = Some(Duration::from_secs(1));
+        sound.fade_in = Duration::from_millis(100);
+        sound.fade_out = Duration::from_millis(100);
+        let clip = Clip::load(&sound).unwrap();
+
+        let frame_rate = clip.sample_rate as usize * clip.channels as usize;
+        assert_eq!(clip.samples.len(), frame_rate);
+        assert_eq!(clip.samples[0], 0.);
+        assert_eq!(clip.samples[clip.samples.len() - 1], 0.);
+
+        // without fades the samples are the decoded ones
+        sound.fade_in = Duration::ZERO;
+        sound.fade_out = Duration::ZERO;
+        let clip = Clip::load(&sound).unwrap();
+        let decoded: Vec<f32> = decode(&sound.path).unwrap().convert_samples().take(frame_rate).collect();
+        assert_eq!(clip.samples, decoded);
     }
 }
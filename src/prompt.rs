@@ -0,0 +1,199 @@
+use std::{
+    env, fs, path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{as_time, history, session::Phase, snapshot::Snapshot};
+
+/// `$XDG_RUNTIME_DIR/comodo/status.json`, under `/tmp` when it is not set
+pub fn path() -> PathBuf {
+    let base = env::var_os("XDG_RUNTIME_DIR").filter(|v| !v.is_empty()).map(PathBuf::from).unwrap_or(env::temp_dir());
+    base.join("comodo").join("status.json")
+}
+
+/// What the prompt shows, it only changes when the session gets controlled
+/// or moves on to another phase so the daemon seldom has to write it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Segment {
+    pub phase: Phase,
+    pub cycle: u64,
+    pub total: u64,
+    pub paused: bool,
+    /// when the phase ends, unless paused
+    pub ends_at: u64,
+    /// seconds left in the phase when paused
+    pub remaining: u64,
+}
+
+impl Segment {
+    /// `None` while the daemon is idle
+    pub fn new(snapshot: &Snapshot, now: u64) -> Option<Self> {
+        let phase = snapshot.phase.filter(|_| snapshot.running)?;
+        Some(Segment {
+            phase,
+            cycle: snapshot.cycle,
+            total: snapshot.total,
+            paused: snapshot.paused,
+            ends_at: if snapshot.paused { 0 } else { now + snapshot.remaining() },
+            remaining: if snapshot.paused { snapshot.remaining() } else { 0 },
+        })
+    }
+
+    /// `🍅 12:34`, empty once the phase is over
+    pub fn render(&self, now: u64) -> String {
+        let remaining = if self.paused { self.remaining } else { self.ends_at.saturating_sub(now) };
+        if !self.paused && remaining == 0 {
+            return String::new();
+        }
+        let icon = match self.phase {
+            _ if self.paused => "⏸",
+            Phase::Focus => "🍅",
+            Phase::ShortBreak => "☕",
+            Phase::LongBreak => "🌴",
+        };
+        format!("{} {}", icon, as_time(remaining))
+    }
+}
+
+/// The status file the daemon keeps for the prompt
+pub struct StatusFile {
+    path: PathBuf,
+    last: Option<Segment>,
+}
+
+impl StatusFile {
+    /// whatever a previous daemon left behind is removed
+    pub fn new(path: PathBuf) -> Self {
+        history::remove(&path);
+        StatusFile { path, last: None }
+    }
+
+    /// the prompt never reads half of it, see [`history::replace`],
+    /// `None` removes it
+    pub fn update(&mut self, segment: Option<Segment>) {
+        if segment == self.last {
+            return;
+        }
+        let written = match &segment {
+            Some(segment) => serde_json::to_string(segment).map_err(|e| e.to_string())
+                .and_then(|content| history::replace(&self.path, &content)),
+            None => {
+                history::remove(&self.path);
+                Ok(())
+            },
+        };
+        match written {
+            Ok(()) => self.last = segment,
+            Err(e) => eprintln!("could not update the prompt status: {}", e),
+        }
+    }
+}
+
+/// `comodo prompt-segment`, nothing when there is no session or the file
+/// can not be made sense of, a prompt has no use for errors
+pub fn segment(path: &Path, now: u64) -> String {
+    fs::read_to_string(path).ok()
+        .and_then(|content| serde_json::from_str::<Segment>(&content).ok())
+        .map(|segment| segment.render(now))
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Starship,
+}
+
+/// what to add to the config of `shell` to get the segment in the prompt
+pub fn snippet(shell: Shell, status: &Path) -> String {
+    match shell {
+        Shell::Bash => String::from(r#"# add to ~/.bashrc
+__comodo_ps1() {
+    local segment
+    segment="$(comodo prompt-segment)"
+    [ -n "$segment" ] && printf '%s ' "$segment"
+}
+PS1='$(__comodo_ps1)'"$PS1"
+"#),
+        Shell::Zsh => String::from(r#"# add to ~/.zshrc
+setopt PROMPT_SUBST
+__comodo_prompt() {
+    local segment="$(comodo prompt-segment)"
+    [[ -n $segment ]] && print -n "$segment "
+}
+PROMPT='$(__comodo_prompt)'"$PROMPT"
+"#),
+        Shell::Fish => String::from(r#"# add to ~/.config/fish/config.fish
+functions -q __comodo_fish_prompt; or functions -c fish_prompt __comodo_fish_prompt
+function fish_prompt
+    set -l segment (comodo prompt-segment)
+    test -n "$segment"; and echo -n "$segment "
+    __comodo_fish_prompt
+end
+"#),
+        Shell::Starship => format!(r#"# add to ~/.config/starship.toml
+[custom.comodo]
+command = "comodo prompt-segment"
+when = "test -s {}"
+format = "[$output]($style) "
+style = "bold red"
+shell = ["sh"]
+"#, status.display()),
+    }
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use std::{fs, path::Path};
+    #[allow(unused_imports)]
+    use crate::{session::Phase, snapshot::Snapshot};
+    #[allow(unused_imports)]
+    use super::{segment, snippet, Segment, Shell, StatusFile};
+
+    #[test]
+    fn segment_test() {
+        let snapshot = Snapshot {
+            running: true,
+            phase: Some(Phase::Focus),
+            cycle: 1,
+            total: 4,
+            elapsed: 100,
+            length: 1500,
+            ..Default::default()
+        };
+        let focus = Segment::new(&snapshot, 1000).unwrap();
+        assert_eq!(focus.ends_at, 2400);
+        // a tick later the segment is the same, the file is left alone
+        assert_eq!(Segment::new(&Snapshot { elapsed: 101, ..snapshot.clone() }, 1001), Some(focus.clone()));
+        assert_eq!(Segment::new(&Snapshot::idle(0), 1000), None);
+
+        let dir = std::env::temp_dir().join(format!("comodo-prompt-{}", std::process::id()));
+        let path = dir.join("status.json");
+        assert_eq!(segment(&path, 1000), "");
+        let mut file = StatusFile::new(path.clone());
+        file.update(Some(focus));
+        assert_eq!(segment(&path, 1000), "🍅 23:20");
+        assert_eq!(segment(&path, 2400), "");
+
+        let paused = Segment::new(&Snapshot { paused: true, ..snapshot }, 1000);
+        file.update(paused);
+        assert_eq!(segment(&path, 5000), "⏸ 23:20");
+
+        file.update(None);
+        assert!(!path.exists());
+        fs::remove_dir(dir).unwrap();
+    }
+
+    #[test]
+    fn snippet_test() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::Starship] {
+            assert!(snippet(shell, Path::new("/run/user/1000/comodo/status.json")).contains("comodo prompt-segment"));
+        }
+        assert!(snippet(Shell::Starship, Path::new("/run/user/1000/comodo/status.json"))
+                .contains("when = \"test -s /run/user/1000/comodo/status.json\""));
+    }
+}
@@ -0,0 +1,107 @@
+//! What goes over the sockets of the daemon, a connection per request, the
+//! first byte says what it is about and some take more after it
+
+use std::{io::{self, Read, Write}, os::unix::net::UnixStream};
+
+use crate::{config::Config, now_in_secs};
+
+/// where sessions get started
+pub const SOCKET_PATH: &str = "/tmp/comodo.sock";
+/// where the running session gets controlled
+pub const STATE_PATH: &str = "/tmp/state.sock";
+/// the notifications, a JSON line each
+pub const WATCH_PATH: &str = "/tmp/comodo.watch.sock";
+/// a snapshot as a JSON line per timer every tick, the default timer closes
+/// the tick whether it runs or not
+pub const STATUS_PATH: &str = "/tmp/comodo.status.sock";
+
+pub const DEFAULT: [u8;1] = [0];
+pub const PAUSE: [u8;1] = [1];
+pub const RESUME: [u8;1] = [2];
+pub const STOP: [u8;1] = [3];
+pub const KILL: [u8;1] = [4];
+pub const ACK: [u8;1] = [5];
+pub const SKIP: [u8;1] = [6];
+/// followed by the number of seconds to extend the current phase by
+pub const EXTEND: [u8;1] = [7];
+/// followed by the flags below and the note until the end of the stream
+pub const INTERRUPT: [u8;1] = [8];
+pub const INTERRUPT_EXTERNAL: u8 = 1;
+pub const INTERRUPT_PAUSE: u8 = 2;
+/// followed by the task name and its tags, a line each, until the end of the stream
+pub const TASK: [u8;1] = [9];
+
+/// followed by the name of a timer and a newline, then the request for that
+/// timer, on the start and the state sockets alike
+pub const TIMER: [u8;1] = [10];
+
+/// the timer of the requests that name none
+pub const DEFAULT_TIMER: &str = "default";
+
+/// for the command line, a timer name has to fit on a line
+pub fn parse_timer(name: &str) -> Result<String, String> {
+    if name.is_empty() || name.contains('\n') {
+        return Err(String::from("a timer name is a single non-empty line"));
+    }
+    Ok(name.to_string())
+}
+
+/// `request` for the timer `name`, the default one goes without a prefix
+pub fn to_timer(name: &str, request: &[u8]) -> Vec<u8> {
+    if name == DEFAULT_TIMER {
+        return request.to_vec();
+    }
+    [&TIMER[..], name.as_bytes(), b"\n", request].concat()
+}
+
+/// the timer a request is for and its first byte
+pub fn read_timer(stream: &mut impl Read) -> io::Result<(String, [u8;1])> {
+    let mut first = [0];
+    stream.read_exact(&mut first)?;
+    if first != TIMER {
+        return Ok((String::from(DEFAULT_TIMER), first));
+    }
+    let mut name = vec![];
+    loop {
+        stream.read_exact(&mut first)?;
+        if first == *b"\n" {
+            break;
+        }
+        name.push(first[0]);
+    }
+    stream.read_exact(&mut first)?;
+    Ok((String::from_utf8_lossy(&name).into_owned(), first))
+}
+
+/// hands a session of the timer `timer` starting now to the daemon, it is
+/// picked up once the running one of that timer is over
+pub fn send_start(socket_path: &str, timer: &str, config: &Config) -> io::Result<()> {
+    let mut stream = UnixStream::connect(socket_path)?;
+    let in_ms = now_in_secs();
+
+    stream.write_all(&to_timer(timer, &DEFAULT))?;
+    stream.write_all(&in_ms.to_be_bytes())?;
+    // the rest of the stream is the config itself
+    stream.write_all(config.to_stream_string().map_err(io::Error::other)?.as_bytes())?;
+    stream.shutdown(std::net::Shutdown::Both)
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use super::{parse_timer, read_timer, to_timer, DEFAULT_TIMER, PAUSE, STOP};
+
+    #[test]
+    fn timer_test() {
+        assert_eq!(to_timer(DEFAULT_TIMER, &PAUSE), PAUSE);
+        let request = [&to_timer("tea", &STOP)[..], b"rest"].concat();
+        let mut reader = &request[..];
+        assert_eq!(read_timer(&mut reader).unwrap(), (String::from("tea"), STOP));
+        assert_eq!(reader, b"rest");
+        assert_eq!(read_timer(&mut &PAUSE[..]).unwrap(), (String::from(DEFAULT_TIMER), PAUSE));
+        assert!(read_timer(&mut &b""[..]).is_err());
+
+        assert_eq!(parse_timer("tea"), Ok(String::from("tea")));
+        assert!(parse_timer("").is_err());
+        assert!(parse_timer("tea\ntime").is_err());
+    }
+}
@@ -0,0 +1,323 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Comodo;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Focus,
+    ShortBreak,
+    LongBreak,
+}
+
+impl Phase {
+    /// as serialized, what scripts and other programs get
+    pub fn id(&self) -> &'static str {
+        match self {
+            Phase::Focus => "focus",
+            Phase::ShortBreak => "short_break",
+            Phase::LongBreak => "long_break",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Focus => "focus",
+            Phase::ShortBreak => "rest",
+            Phase::LongBreak => "long rest",
+        }
+    }
+}
+
+/// What happened since the last [`Session::tick`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// `cycle` is 1 based
+    PhaseStarted { phase: Phase, cycle: u64 },
+    Paused,
+    Resumed,
+    SessionComplete,
+}
+
+/// When an event of the last tick actually happened, the ticks can be far
+/// apart, and how long the phase it started is planned for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    pub at: u64,
+    pub planned: Duration,
+}
+
+/// A snapshot of the session at a given time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Status {
+    pub phase: Phase,
+    pub cycle: u64,
+    pub total: u64,
+    pub elapsed: Duration,
+    pub length: Duration,
+    pub paused: bool,
+}
+
+//  cycle 1           cycle 2           ...  cycle 4
+// [focus][rest]     [focus][rest]      ...  [focus][long rest]
+//
+// every `long_break_interval`th cycle ends with a long rest instead of a short one
+pub fn schedule(comodo: &Comodo) -> Vec<(Phase, Duration)> {
+    let mut phases = vec![];
+    for cycle in 1..=comodo.iterations as u64 {
+        phases.push((Phase::Focus, comodo.focus));
+        if comodo.long_break_interval != 0 && cycle % comodo.long_break_interval as u64 == 0 {
+            phases.push((Phase::LongBreak, comodo.big_rest));
+        } else {
+            phases.push((Phase::ShortBreak, comodo.rest));
+        }
+    }
+    phases
+}
+
+/// The timer state of a single pomodoro session, everything is counted in
+/// seconds since the unix epoch so a session can be driven by any clock
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    phases: Vec<(Phase, Duration)>,
+    started: u64,
+    paused_duration: u64,
+    paused_at: Option<u64>,
+    /// what was left of the phases that got skipped
+    skipped: u64,
+    /// index of the phase the last tick reported
+    current: Option<usize>,
+    complete: bool,
+}
+
+impl Session {
+    pub fn new(comodo: &Comodo, started: u64) -> Self {
+        Session {
+            phases: schedule(comodo),
+            started,
+            paused_duration: 0,
+            paused_at: None,
+            skipped: 0,
+            current: None,
+            complete: false,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.phases.iter().filter(|(phase, _)| *phase == Phase::Focus).count() as u64
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// seconds the timer actually ran for, skipped ones included
+    fn active(&self, now: u64) -> u64 {
+        let until = self.paused_at.unwrap_or(now);
+        until.saturating_sub(self.started).saturating_sub(self.paused_duration) + self.skipped
+    }
+
+    /// the phase index and how far into it we are, `None` once every phase is over
+    fn locate(&self, now: u64) -> Option<(usize, u64)> {
+        let mut active = self.active(now);
+        for (i, (_, length)) in self.phases.iter().enumerate() {
+            if active < length.as_secs() {
+                return Some((i, active));
+            }
+            active -= length.as_secs();
+        }
+        None
+    }
+
+    // the time it was `now` minus how far the session got since `active`
+    fn time_at(&self, active: u64, now: u64) -> u64 {
+        now.saturating_sub(self.active(now).saturating_sub(active))
+    }
+
+    fn cycle_of(&self, index: usize) -> u64 {
+        self.phases[..=index].iter().filter(|(phase, _)| *phase == Phase::Focus).count() as u64
+    }
+
+    pub fn pause(&mut self, now: u64) -> Option<Event> {
+        if self.paused_at.is_some() || self.complete {
+            return None;
+        }
+        self.paused_at = Some(now);
+        Some(Event::Paused)
+    }
+
+    pub fn resume(&mut self, now: u64) -> Option<Event> {
+        let paused_at = self.paused_at.take()?;
+        self.paused_duration += now.saturating_sub(paused_at);
+        Some(Event::Resumed)
+    }
+
+    /// time the timer should not have run for, the daemon being down for instance
+    pub fn downtime(&mut self, by: Duration) {
+        // a paused session accounts for it already once resumed
+        if self.paused_at.is_none() {
+            self.paused_duration += by.as_secs();
+        }
+    }
+
+    /// jump to the start of the next phase, the next tick reports it
+    pub fn skip(&mut self, now: u64) {
+        if let Some((index, elapsed)) = self.locate(now) {
+            self.skipped += self.phases[index].1.as_secs() - elapsed;
+        }
+    }
+
+    /// make the current phase longer
+    pub fn extend(&mut self, now: u64, by: Duration) {
+        if let Some((index, _)) = self.locate(now) {
+            self.phases[index].1 += by;
+        }
+    }
+
+    /// advance the session to `now`, reporting the phase changes
+    ///
+    /// after a gap longer than a phase (a suspend, a stalled daemon) every
+    /// phase crossed is reported, in order
+    pub fn tick(&mut self, now: u64) -> Vec<Event> {
+        let mut events = vec![];
+        if self.complete {
+            return events;
+        }
+        let from = self.current.map_or(0, |current| current + 1);
+        let located = self.locate(now);
+        let to = located.map_or(self.phases.len(), |(index, _)| index + 1);
+        for index in from..to {
+            events.push(Event::PhaseStarted { phase: self.phases[index].0, cycle: self.cycle_of(index) });
+        }
+        match located {
+            Some((index, _)) => self.current = Some(index),
+            None => {
+                self.complete = true;
+                events.push(Event::SessionComplete);
+            },
+        }
+        events
+    }
+
+    /// `event` being one the tick at `now` reported, a phase crossed while the
+    /// ticks were apart started and ended before `now`
+    pub fn timing(&self, event: Event, now: u64) -> Timing {
+        let offset = |index: usize| self.phases[..index].iter().map(|(_, length)| length.as_secs()).sum::<u64>();
+        match event {
+            Event::PhaseStarted { phase, cycle } => {
+                let index = (0..self.phases.len()).find(|&i| self.phases[i].0 == phase && self.cycle_of(i) == cycle);
+                match index {
+                    Some(index) => Timing { at: self.time_at(offset(index), now), planned: self.phases[index].1 },
+                    None => Timing { at: now, planned: Duration::ZERO },
+                }
+            },
+            Event::SessionComplete => Timing { at: self.time_at(offset(self.phases.len()), now), planned: Duration::ZERO },
+            Event::Paused | Event::Resumed => Timing { at: now, planned: Duration::ZERO },
+        }
+    }
+
+    pub fn status(&self, now: u64) -> Option<Status> {
+        let (index, elapsed) = self.locate(now)?;
+        let (phase, length) = self.phases[index];
+        Some(Status {
+            phase,
+            cycle: self.cycle_of(index),
+            total: self.total(),
+            elapsed: Duration::from_secs(elapsed),
+            length,
+            paused: self.is_paused(),
+        })
+    }
+
+    /// how far into the whole session we are, and how long it lasts
+    pub fn progress(&self, now: u64) -> (Duration, Duration) {
+        let length: Duration = self.phases.iter().map(|(_, length)| *length).sum();
+        (Duration::from_secs(self.active(now)).min(length), length)
+    }
+
+    /// the phase following the current one, `None` when the current one is the last
+    pub fn next_phase(&self, now: u64) -> Option<Phase> {
+        let (index, _) = self.locate(now)?;
+        self.phases.get(index + 1).map(|(phase, _)| *phase)
+    }
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use std::time::Duration;
+    #[allow(unused_imports)]
+    use crate::config::Config;
+    #[allow(unused_imports)]
+    use super::{Event, Phase, Session};
+
+    #[allow(dead_code)]
+    fn config() -> Config {
+        toml::de::from_str(r#"
+            [comodo]
+            iterations = 4
+            focus = "00:10"
+            rest = "00:02"
+            big_rest = "00:05"
+            long_break_interval = 2
+        "#).unwrap()
+    }
+
+    #[test]
+    fn transitions_test() {
+        let mut session = Session::new(&config().comodo, 100);
+        assert_eq!(session.tick(100), vec![Event::PhaseStarted { phase: Phase::Focus, cycle: 1 }]);
+        assert_eq!(session.tick(105), vec![]);
+        assert_eq!(session.tick(110), vec![Event::PhaseStarted { phase: Phase::ShortBreak, cycle: 1 }]);
+        assert_eq!(session.tick(112), vec![Event::PhaseStarted { phase: Phase::Focus, cycle: 2 }]);
+        assert_eq!(session.tick(122), vec![Event::PhaseStarted { phase: Phase::LongBreak, cycle: 2 }]);
+        // 4 cycles of 10s focus, two short rests and two long ones
+        // the phases crossed in between are reported too
+        assert_eq!(session.tick(100 + 40 + 4 + 10 - 1), vec![
+            Event::PhaseStarted { phase: Phase::Focus, cycle: 3 },
+            Event::PhaseStarted { phase: Phase::ShortBreak, cycle: 3 },
+            Event::PhaseStarted { phase: Phase::Focus, cycle: 4 },
+            Event::PhaseStarted { phase: Phase::LongBreak, cycle: 4 },
+        ]);
+        // each when it was due rather than when the tick came
+        let timing = session.timing(Event::PhaseStarted { phase: Phase::ShortBreak, cycle: 3 }, 153);
+        assert_eq!((timing.at, timing.planned), (137, Duration::from_secs(2)));
+        assert_eq!(session.timing(Event::SessionComplete, 160).at, 154);
+        assert_eq!(session.tick(100 + 40 + 4 + 10), vec![Event::SessionComplete]);
+        assert_eq!(session.tick(200), vec![]);
+    }
+
+    #[test]
+    fn pause_test() {
+        let mut session = Session::new(&config().comodo, 0);
+        session.tick(0);
+        assert_eq!(session.pause(5), Some(Event::Paused));
+        assert_eq!(session.pause(6), None);
+        assert_eq!(session.status(60).unwrap().elapsed, Duration::from_secs(5));
+        assert_eq!(session.resume(60), Some(Event::Resumed));
+        assert_eq!(session.tick(64), vec![]);
+        assert_eq!(session.tick(65), vec![Event::PhaseStarted { phase: Phase::ShortBreak, cycle: 1 }]);
+        assert_eq!(session.next_phase(65), Some(Phase::Focus));
+    }
+
+    #[test]
+    fn skip_extend_test() {
+        let mut session = Session::new(&config().comodo, 0);
+        session.tick(0);
+        session.skip(3);
+        assert_eq!(session.tick(3), vec![Event::PhaseStarted { phase: Phase::ShortBreak, cycle: 1 }]);
+        assert_eq!(session.status(3).unwrap().elapsed, Duration::ZERO);
+
+        session.extend(4, Duration::from_secs(5));
+        assert_eq!(session.status(4).unwrap().length, Duration::from_secs(7));
+        // 4 cycles of 10s focus, two 2s rests and two 5s long rests, 5s more for the extension
+        assert_eq!(session.progress(4), (Duration::from_secs(11), Duration::from_secs(59)));
+        assert_eq!(session.tick(9), vec![]);
+        assert_eq!(session.tick(10), vec![Event::PhaseStarted { phase: Phase::Focus, cycle: 2 }]);
+    }
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    as_time,
+    config::{Colors, RGB},
+    protocol::DEFAULT_TIMER,
+    session::{Phase, Session},
+};
+
+/// What the daemon publishes on the status stream, a json line every tick
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// `None` for the default timer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timer: Option<String>,
+    /// `false` while the daemon waits for a session, nothing else is set then
+    pub running: bool,
+    pub phase: Option<Phase>,
+    pub cycle: u64,
+    pub total: u64,
+    /// seconds into the phase
+    pub elapsed: u64,
+    /// seconds the phase lasts
+    pub length: u64,
+    /// seconds into the session and how many it lasts
+    pub session_elapsed: u64,
+    pub session_length: u64,
+    pub paused: bool,
+    pub task: Option<String>,
+    pub tags: Vec<String>,
+    /// of the current focus phase
+    pub interruptions: u32,
+    /// focus phases completed today
+    pub today: u64,
+    /// what the config paints the phase with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<RGB>,
+}
+
+impl Snapshot {
+    pub fn idle(today: u64) -> Self {
+        Snapshot { today, ..Default::default() }
+    }
+
+    pub fn new(session: &Session, task: Option<&str>, tags: &[String], interruptions: u32, today: u64, colors: &Colors, now: u64) -> Self {
+        let Some(status) = session.status(now) else { return Snapshot::idle(today) };
+        let (session_elapsed, session_length) = session.progress(now);
+        Snapshot {
+            timer: None,
+            running: true,
+            phase: Some(status.phase),
+            cycle: status.cycle,
+            total: status.total,
+            elapsed: status.elapsed.as_secs(),
+            length: status.length.as_secs(),
+            session_elapsed: session_elapsed.as_secs(),
+            session_length: session_length.as_secs(),
+            paused: status.paused,
+            task: task.map(String::from),
+            tags: tags.to_vec(),
+            interruptions,
+            today,
+            color: Some(colors.for_phase(status.phase, status.paused)),
+        }
+    }
+
+    /// seconds left in the phase
+    pub fn remaining(&self) -> u64 {
+        self.length.saturating_sub(self.elapsed)
+    }
+
+    pub fn timer(&self) -> &str {
+        self.timer.as_deref().unwrap_or(DEFAULT_TIMER)
+    }
+
+    /// a line for `comodo status`, as `tea: focus 1/1, 03:12 left - green`
+    pub fn describe(&self) -> String {
+        let Some(phase) = self.phase.filter(|_| self.running) else {
+            return format!("{}: idle", self.timer());
+        };
+        let task = self.task.as_ref().map(|t| format!(" - {}", t)).unwrap_or_default();
+        let interruptions = match self.interruptions {
+            0 => String::new(),
+            1 => String::from(", 1 interruption"),
+            n => format!(", {} interruptions", n),
+        };
+        format!("{}: {} {}/{}, {} left{}{}{}", self.timer(), phase.name(), self.cycle, self.total, as_time(self.remaining()),
+                if self.paused { " (paused)" } else { "" }, interruptions, task)
+    }
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use crate::{config::{Colors, Config}, session::{Phase, Session}};
+    #[allow(unused_imports)]
+    use super::Snapshot;
+
+    #[test]
+    fn snapshot_test() {
+        let config: Config = toml::de::from_str(r#"
+            [comodo]
+            iterations = 2
+            focus = "25:00"
+            rest = "05:00"
+            big_rest = "15:00"
+        "#).unwrap();
+        let mut session = Session::new(&config.comodo, 0);
+        session.tick(0);
+
+        let snapshot = Snapshot::new(&session, Some("review"), &[String::from("work")], 1, 3, &Colors::default(), 60);
+        assert!(snapshot.running);
+        assert_eq!((snapshot.phase, snapshot.cycle, snapshot.total), (Some(Phase::Focus), 1, 2));
+        assert_eq!((snapshot.remaining(), snapshot.session_elapsed, snapshot.session_length), (1440, 60, 3600));
+        assert_eq!(snapshot.color, Some(Colors::default().focus));
+
+        let line = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(serde_json::from_str::<Snapshot>(&line).unwrap(), snapshot);
+
+        let done = Snapshot::new(&session, None, &[], 0, 3, &Colors::default(), 10_000);
+        assert_eq!(done, Snapshot::idle(3));
+        assert!(!done.running);
+        assert_eq!(done.describe(), "default: idle");
+
+        let tea = Snapshot { timer: Some(String::from("tea")), paused: true, ..snapshot };
+        assert_eq!(tea.describe(), "tea: focus 1/2, 24:00 left (paused), 1 interruption - review");
+        let line = serde_json::to_string(&tea).unwrap();
+        assert_eq!(serde_json::from_str::<Snapshot>(&line).unwrap().timer(), "tea");
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{history::{Outcome, Record}, session::Phase};
+
+const BAR_WIDTH: u64 = 30;
+const NO_TASK: &str = "(no task)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum View {
+    Today,
+    /// since monday
+    Week,
+    /// since the first of the month
+    Month,
+}
+
+impl View {
+    pub fn range(&self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let from = match self {
+            View::Today => today,
+            View::Week => today - Days::new(today.weekday().num_days_from_monday() as u64),
+            View::Month => today.with_day(1).unwrap_or(today),
+        };
+        (from, today)
+    }
+}
+
+/// dates are given as `YYYY-MM-DD`
+pub fn parse_date(s: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|e| format!("invalid date {:?}: {}", s, e))
+}
+
+/// the local day a record started on
+pub fn day_of(record: &Record) -> Option<NaiveDate> {
+    DateTime::parse_from_rfc3339(&record.start).ok().map(|start| start.with_timezone(&Local).date_naive())
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Tally {
+    pub name: String,
+    /// completed pomodoros
+    pub completed: u64,
+    /// seconds spent focusing, finished or not
+    pub focus: u64,
+    pub interruptions: u64,
+}
+
+impl Tally {
+    fn add(&mut self, record: &Record) {
+        if record.outcome == Outcome::Completed {
+            self.completed += 1;
+        }
+        self.focus += record.actual;
+        self.interruptions += record.interruptions as u64;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub completed: u64,
+    /// seconds
+    pub focus: u64,
+    pub interruptions: u64,
+    /// per focus phase
+    pub average_interruptions: f64,
+    /// completed focus phases over the started ones, from 0 to 1
+    pub completion_rate: f64,
+    /// the most days in a row with at least a completed pomodoro
+    pub longest_streak: u64,
+    pub days: Vec<Tally>,
+    pub tasks: Vec<Tally>,
+    pub tags: Vec<Tally>,
+}
+
+impl Report {
+    /// only the focus phases started between `from` and `to`, both included, are counted
+    pub fn new(records: &[Record], from: NaiveDate, to: NaiveDate) -> Self {
+        let mut days = BTreeMap::new();
+        let mut tasks: BTreeMap<String, Tally> = BTreeMap::new();
+        let mut tags: BTreeMap<String, Tally> = BTreeMap::new();
+        let (mut started, mut interruptions) = (0, 0);
+
+        for day in from.iter_days().take_while(|day| *day <= to) {
+            days.insert(day, Tally { name: day.to_string(), ..Default::default() });
+        }
+        for record in records.iter().filter(|r| r.phase == Phase::Focus) {
+            let Some(tally) = day_of(record).and_then(|day| days.get_mut(&day)) else { continue };
+            tally.add(record);
+            started += 1;
+            interruptions += record.interruptions as u64;
+
+            let task = record.task.clone().unwrap_or_else(|| String::from(NO_TASK));
+            tasks.entry(task.clone()).or_insert_with(|| Tally { name: task, ..Default::default() }).add(record);
+            for tag in &record.tags {
+                tags.entry(tag.clone()).or_insert_with(|| Tally { name: tag.clone(), ..Default::default() }).add(record);
+            }
+        }
+
+        let days: Vec<Tally> = days.into_values().collect();
+        let completed = days.iter().map(|d| d.completed).sum();
+        let longest_streak = days.iter()
+            .fold((0, 0), |(longest, current), day| {
+                let current = if day.completed > 0 { current + 1 } else { 0 };
+                (longest.max(current), current)
+            }).0;
+        let ratio = |a: u64, b: u64| if b == 0 { 0. } else { a as f64 / b as f64 };
+
+        Report {
+            from,
+            to,
+            completed,
+            focus: days.iter().map(|d| d.focus).sum(),
+            interruptions,
+            average_interruptions: ratio(interruptions, started),
+            completion_rate: ratio(completed, started),
+            longest_streak,
+            days,
+            tasks: tasks.into_values().collect(),
+            tags: tags.into_values().collect(),
+        }
+    }
+}
+
+/// `1h05m`, `25m`
+pub fn as_hours(seconds: u64) -> String {
+    let (hours, minutes) = (seconds / 3600, seconds % 3600 / 60);
+    if hours > 0 {
+        format!("{}h{:02}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+fn bars(tallies: &[Tally]) -> String {
+    let most = tallies.iter().map(|t| t.completed).max().unwrap_or(0).max(1);
+    let width = tallies.iter().map(|t| t.name.chars().count()).max().unwrap_or(0);
+    let mut out = String::new();
+    for tally in tallies {
+        let length = (tally.completed * BAR_WIDTH).div_ceil(most) as usize;
+        let mut line = format!("  {:<width$}  {:<bar$}  {:>3}  {:<6}",
+                               tally.name, "█".repeat(length), tally.completed, as_hours(tally.focus),
+                               width = width, bar = BAR_WIDTH as usize);
+        if tally.interruptions > 0 {
+            line += &format!("  {} interrupted", tally.interruptions);
+        }
+        out += line.trim_end();
+        out.push('\n');
+    }
+    out
+}
+
+pub fn render(report: &Report) -> String {
+    let mut out = format!("{} .. {}\n", report.from, report.to);
+    out += &format!("  completed pomodoros  {}\n", report.completed);
+    out += &format!("  focus time           {}\n", as_hours(report.focus));
+    out += &format!("  interruptions        {} ({:.2} per pomodoro)\n", report.interruptions, report.average_interruptions);
+    out += &format!("  completion rate      {:.0}%\n", report.completion_rate * 100.);
+    out += &format!("  longest streak       {} day{}\n", report.longest_streak, if report.longest_streak == 1 { "" } else { "s" });
+    if report.days.len() > 1 {
+        out += "\nper day\n";
+        out += &bars(&report.days);
+    }
+    if !report.tasks.is_empty() {
+        out += "\nper task\n";
+        out += &bars(&report.tasks);
+    }
+    if !report.tags.is_empty() {
+        out += "\nper tag\n";
+        out += &bars(&report.tags);
+    }
+    out
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use chrono::NaiveDate;
+    #[allow(unused_imports)]
+    use crate::{history::{Outcome, Record}, session::Phase};
+    #[allow(unused_imports)]
+    use super::{as_hours, parse_date, render, Report, View};
+
+    #[allow(dead_code)]
+    fn record(start: &str, phase: Phase, outcome: Outcome, task: Option<&str>, tags: &[&str]) -> Record {
+        Record {
+            phase,
+            outcome,
+            end: format!("{}T10:25:00+00:00", start),
+            actual: if outcome == Outcome::Completed { 1500 } else { 600 },
+            task: task.map(String::from),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            interruptions: 1,
+            ..Record::sample(&format!("{}T10:00:00+00:00", start))
+        }
+    }
+
+    #[test]
+    fn view_test() {
+        let today = parse_date("2026-10-15").unwrap();
+        assert_eq!(View::Today.range(today), (today, today));
+        assert_eq!(View::Week.range(today).0, parse_date("2026-10-12").unwrap());
+        assert_eq!(View::Month.range(today).0, parse_date("2026-10-01").unwrap());
+        assert!(parse_date("15/10/2026").is_err());
+    }
+
+    #[test]
+    fn report_test() {
+        let records = vec![
+            record("2026-10-10", Phase::Focus, Outcome::Completed, Some("report"), &["work"]),
+            record("2026-10-11", Phase::Focus, Outcome::Completed, Some("report"), &["work", "writing"]),
+            record("2026-10-11", Phase::ShortBreak, Outcome::Completed, None, &[]),
+            record("2026-10-12", Phase::Focus, Outcome::Completed, None, &[]),
+            record("2026-10-12", Phase::Focus, Outcome::Interrupted, None, &[]),
+            record("2026-10-14", Phase::Focus, Outcome::Completed, Some("review"), &["work"]),
+            // out of the range
+            record("2026-10-20", Phase::Focus, Outcome::Completed, None, &[]),
+        ];
+        let report = Report::new(&records, parse_date("2026-10-10").unwrap(), parse_date("2026-10-15").unwrap());
+        assert_eq!(report.completed, 4);
+        assert_eq!(report.focus, 4 * 1500 + 600);
+        assert_eq!(report.interruptions, 5);
+        assert_eq!(report.average_interruptions, 1.);
+        assert_eq!(report.completion_rate, 0.8);
+        assert_eq!(report.longest_streak, 3);
+        assert_eq!(report.days.iter().map(|d| d.interruptions).collect::<Vec<_>>(), vec![1, 1, 2, 0, 1, 0]);
+        assert_eq!(report.days.len(), 6);
+        assert_eq!(report.tasks.iter().map(|t| (t.name.as_str(), t.completed)).collect::<Vec<_>>(),
+                   vec![("(no task)", 1), ("report", 2), ("review", 1)]);
+        assert_eq!(report.tags.iter().map(|t| (t.name.as_str(), t.completed)).collect::<Vec<_>>(),
+                   vec![("work", 3), ("writing", 1)]);
+
+        let text = render(&report);
+        assert!(text.contains("completed pomodoros  4"));
+        assert!(text.contains("completion rate      80%"));
+        assert!(text.contains(&format!("  {:<9}  {}    2  50m     2 interrupted\n", "report", "█".repeat(30))));
+
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["from"], "2026-10-10");
+        assert_eq!(json["longest_streak"], 3);
+    }
+
+    #[test]
+    fn as_hours_test() {
+        assert_eq!(as_hours(25 * 60), "25m");
+        assert_eq!(as_hours(3600 + 5 * 60 + 59), "1h05m");
+    }
+}
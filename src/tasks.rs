@@ -0,0 +1,128 @@
+use std::{
+    fs, io::ErrorKind, path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    history::{self, Outcome, Record},
+    session::Phase,
+};
+
+pub fn path() -> Option<PathBuf> {
+    history::data_dir().map(|dir| dir.join("tasks.json"))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Task {
+    pub id: u32,
+    pub name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// in pomodoros
+    #[serde(default)]
+    pub estimate: Option<u32>,
+    #[serde(default)]
+    pub done: bool,
+}
+
+/// The tasks of `comodo task`, kept as a json file in the data dir
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskList {
+    pub tasks: Vec<Task>,
+}
+
+impl TaskList {
+    /// a missing file is an empty list
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| format!("invalid task list {}: {}", path.display(), e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(TaskList::default()),
+            Err(e) => Err(format!("could not read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        history::replace(path, &serde_json::to_string_pretty(self).map_err(|e| e.to_string())?)
+    }
+
+    pub fn add(&mut self, name: &str, tags: Vec<String>, estimate: Option<u32>) -> &Task {
+        let id = self.tasks.iter().map(|t| t.id).max().unwrap_or(0) + 1;
+        self.tasks.push(Task { id, name: name.to_string(), tags, estimate, done: false });
+        self.tasks.last().unwrap()
+    }
+
+    pub fn get_mut(&mut self, id: u32) -> Result<&mut Task, String> {
+        self.tasks.iter_mut().find(|t| t.id == id).ok_or(format!("there is no task {}", id))
+    }
+
+    /// `task` is either the id of a task of the list or a free label
+    pub fn find(&self, task: &str) -> Option<&Task> {
+        let id = task.parse::<u32>().ok()?;
+        self.tasks.iter().find(|t| t.id == id)
+    }
+}
+
+/// the name and tags of `task`, looked up in the task list when it is an id of it
+pub fn resolve(task: &str) -> Result<(String, Vec<String>), String> {
+    let list = match path() {
+        Some(path) => TaskList::load(&path)?,
+        None => TaskList::default(),
+    };
+    Ok(match list.find(task) {
+        Some(found) => (found.name.clone(), found.tags.clone()),
+        None => (task.to_string(), vec![]),
+    })
+}
+
+/// the pomodoros completed for a task according to the history
+pub fn actual(records: &[Record], name: &str) -> u64 {
+    records.iter()
+        .filter(|r| r.phase == Phase::Focus && r.outcome == Outcome::Completed && r.task.as_deref() == Some(name))
+        .count() as u64
+}
+
+pub fn render(list: &TaskList, records: &[Record], all: bool) -> String {
+    let mut out = String::new();
+    for task in list.tasks.iter().filter(|t| all || !t.done) {
+        let estimate = task.estimate.map_or(String::from("-"), |e| e.to_string());
+        let tags: String = task.tags.iter().map(|t| format!(" +{}", t)).collect();
+        out += &format!("{:>3} [{}] {}/{} {}{}\n",
+                        task.id, if task.done { "x" } else { " " },
+                        actual(records, &task.name), estimate, task.name, tags);
+    }
+    out
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use std::fs;
+    #[allow(unused_imports)]
+    use crate::history::{Outcome, Record};
+    #[allow(unused_imports)]
+    use super::{actual, render, TaskList};
+
+    #[test]
+    fn list_test() {
+        let path = std::env::temp_dir().join(format!("comodo-tasks-{}", std::process::id())).join("tasks.json");
+        let mut list = TaskList::load(&path).unwrap();
+        assert!(list.tasks.is_empty());
+
+        assert_eq!(list.add("review PR 42", vec![String::from("review")], Some(2)).id, 1);
+        assert_eq!(list.add("write report", vec![], None).id, 2);
+        list.get_mut(2).unwrap().done = true;
+        assert!(list.get_mut(3).is_err());
+        list.save(&path).unwrap();
+
+        let list = TaskList::load(&path).unwrap();
+        assert_eq!(list.find("1").map(|t| t.name.as_str()), Some("review PR 42"));
+        assert_eq!(list.find("review PR 42"), None);
+
+        let record = Record { task: Some(String::from("review PR 42")), ..Record::sample("2026-10-19T09:00:00+00:00") };
+        let records = vec![record.clone(), Record { outcome: Outcome::Skipped, ..record.clone() }, record];
+        assert_eq!(actual(&records, "review PR 42"), 2);
+        assert_eq!(render(&list, &records, false), "  1 [ ] 2/2 review PR 42 +review\n");
+        assert_eq!(render(&list, &records, true).lines().nth(1), Some("  2 [x] 0/- write report"));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,227 @@
+use std::{fmt, time::Duration};
+
+use crate::as_time;
+
+//                     Template::new
+// "cycle {cycle}/{total}" ---------------> [Literal, Cycle, Literal, Total]
+//                     Template::render
+// [Literal, Cycle, ...]   ---------------> "cycle 2/4"
+//
+// a bare `#` is kept as a shorthand for `{cycle}`, use `\#`, `\{`, `\}` and `\\`
+// to get the literal characters
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placeholder {
+    Cycle,
+    Total,
+    RemainingCycles,
+    PhaseLength,
+    NextPhase,
+    Task,
+    TodayCount,
+    Time,
+}
+
+impl Placeholder {
+    pub const ALL: [Placeholder; 8] = [
+        Placeholder::Cycle,
+        Placeholder::Total,
+        Placeholder::RemainingCycles,
+        Placeholder::PhaseLength,
+        Placeholder::NextPhase,
+        Placeholder::Task,
+        Placeholder::TodayCount,
+        Placeholder::Time,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Placeholder::Cycle => "cycle",
+            Placeholder::Total => "total",
+            Placeholder::RemainingCycles => "remaining_cycles",
+            Placeholder::PhaseLength => "phase_length",
+            Placeholder::NextPhase => "next_phase",
+            Placeholder::Task => "task",
+            Placeholder::TodayCount => "today_count",
+            Placeholder::Time => "time",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Placeholder::ALL.into_iter().find(|p| p.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    UnknownPlaceholder(String),
+    UnclosedPlaceholder(usize),
+    UnexpectedBrace(usize),
+    DanglingEscape,
+    InvalidEscape(char),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::UnknownPlaceholder(name) => {
+                let known: Vec<String> = Placeholder::ALL.iter().map(|p| format!("{{{}}}", p.name())).collect();
+                write!(f, "unknown placeholder `{{{}}}`, expected one of {}", name, known.join(", "))
+            },
+            TemplateError::UnclosedPlaceholder(at) => write!(f, "placeholder opened at {} is never closed", at),
+            TemplateError::UnexpectedBrace(at) => write!(f, "unexpected `}}` at {}, use `\\}}` for a literal one", at),
+            TemplateError::DanglingEscape => write!(f, "template ends with a lone `\\`"),
+            TemplateError::InvalidEscape(c) => write!(f, "invalid escape `\\{}`, only `\\#`, `\\{{`, `\\}}` and `\\\\` are allowed", c),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// Values substituted into a [`Template`] when rendering
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    /// 1 based number of the current cycle
+    pub cycle: u64,
+    pub total: u64,
+    pub phase_length: Duration,
+    pub next_phase: String,
+    pub task: String,
+    pub today_count: u64,
+    /// wall clock time, already formatted
+    pub time: String,
+}
+
+impl Context {
+    fn value(&self, placeholder: Placeholder) -> String {
+        match placeholder {
+            Placeholder::Cycle => self.cycle.to_string(),
+            Placeholder::Total => self.total.to_string(),
+            Placeholder::RemainingCycles => self.total.saturating_sub(self.cycle).to_string(),
+            Placeholder::PhaseLength => as_time(self.phase_length.as_secs()),
+            Placeholder::NextPhase => self.next_phase.clone(),
+            Placeholder::Task => self.task.clone(),
+            Placeholder::TodayCount => self.today_count.to_string(),
+            Placeholder::Time => self.time.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn new(source: &str) -> Result<Self, TemplateError> {
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut chars = source.char_indices();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, e @ ('#' | '{' | '}' | '\\'))) => literal.push(e),
+                    Some((_, e)) => return Err(TemplateError::InvalidEscape(e)),
+                    None => return Err(TemplateError::DanglingEscape),
+                },
+                '#' => {
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder(Placeholder::Cycle));
+                },
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) => break,
+                            Some((_, c)) => name.push(c),
+                            None => return Err(TemplateError::UnclosedPlaceholder(i)),
+                        }
+                    }
+                    let placeholder = Placeholder::from_name(name.trim())
+                        .ok_or(TemplateError::UnknownPlaceholder(name))?;
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder(placeholder));
+                },
+                '}' => return Err(TemplateError::UnexpectedBrace(i)),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Template { parts })
+    }
+
+    pub fn render(&self, context: &Context) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(s) => out.push_str(s),
+                Part::Placeholder(p) => out.push_str(&context.value(*p)),
+            }
+        }
+        out
+    }
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use std::time::Duration;
+    #[allow(unused_imports)]
+    use super::{Context, Template, TemplateError};
+
+    #[allow(dead_code)]
+    fn context() -> Context {
+        Context {
+            cycle: 2,
+            total: 4,
+            phase_length: Duration::from_secs(1500),
+            next_phase: String::from("rest"),
+            task: String::from("review PR 42"),
+            today_count: 7,
+            time: String::from("14:05"),
+        }
+    }
+
+    #[test]
+    fn render_test() {
+        let template = Template::new("{cycle}/{total} ({remaining_cycles} left) {phase_length} then {next_phase}").unwrap();
+        assert_eq!(template.render(&context()), "2/4 (2 left) 25:00 then rest");
+
+        let template = Template::new("{task} at {time}, {today_count} today").unwrap();
+        assert_eq!(template.render(&context()), "review PR 42 at 14:05, 7 today");
+    }
+
+    #[test]
+    fn legacy_hash_test() {
+        let template = Template::new("this #").unwrap();
+        assert_eq!(template.render(&context()), "this 2");
+    }
+
+    #[test]
+    fn escape_test() {
+        let template = Template::new(r"issue \#{cycle} \{not a placeholder\} \\").unwrap();
+        assert_eq!(template.render(&context()), r"issue #2 {not a placeholder} \");
+    }
+
+    #[test]
+    fn invalid_test() {
+        assert_eq!(Template::new("{cycles}"), Err(TemplateError::UnknownPlaceholder(String::from("cycles"))));
+        assert_eq!(Template::new("{cycle"), Err(TemplateError::UnclosedPlaceholder(0)));
+        assert_eq!(Template::new("a }"), Err(TemplateError::UnexpectedBrace(2)));
+        assert_eq!(Template::new(r"\n"), Err(TemplateError::InvalidEscape('n')));
+        assert_eq!(Template::new("oops \\"), Err(TemplateError::DanglingEscape));
+    }
+}
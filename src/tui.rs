@@ -0,0 +1,321 @@
+use std::{
+    sync::mpsc::{self, Receiver, TryRecvError}, thread, time::Duration,
+};
+
+use chrono::{Local, NaiveDate};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Clear, Gauge, List, ListState, Paragraph, Sparkline},
+    DefaultTerminal, Frame,
+};
+
+use crate::{
+    as_time, big,
+    client::Client,
+    history::{self, Outcome, Record},
+    notifier::Action,
+    protocol::{DEFAULT_TIMER, RESUME, STOP, TASK},
+    session::Phase,
+    snapshot::Snapshot,
+    stats,
+    tasks::{self, Task, TaskList},
+};
+
+/// days shown by the sparkline, today included
+const DAYS: usize = 14;
+
+const HELP: &str = "space pause/resume  s skip  e +5 min  x stop  t task  q quit";
+
+/// What a key stands for
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Input {
+    Quit,
+    /// open the task picker
+    Pick,
+    /// written as is to the state socket
+    Send(Vec<u8>),
+}
+
+fn input(code: KeyCode, snapshot: &Snapshot) -> Option<Input> {
+    match code {
+        KeyCode::Char('q') | KeyCode::Esc => Some(Input::Quit),
+        KeyCode::Char('t') => Some(Input::Pick),
+        KeyCode::Char(' ') if snapshot.paused => Some(Input::Send(RESUME.to_vec())),
+        KeyCode::Char(' ') | KeyCode::Char('p') => Some(Input::Send(Action::Pause.command())),
+        KeyCode::Char('r') => Some(Input::Send(RESUME.to_vec())),
+        KeyCode::Char('s') => Some(Input::Send(Action::Skip.command())),
+        KeyCode::Char('e') => Some(Input::Send(Action::Extend.command())),
+        KeyCode::Char('x') => Some(Input::Send(STOP.to_vec())),
+        _ => None,
+    }
+}
+
+/// completed pomodoros per day over the `days` up to `today`, the oldest first
+fn daily_counts(records: &[Record], today: NaiveDate, days: usize) -> Vec<u64> {
+    let mut counts = vec![0; days];
+    for record in records.iter().filter(|r| r.phase == Phase::Focus && r.outcome == Outcome::Completed) {
+        let Some(day) = stats::day_of(record) else { continue };
+        let Ok(ago) = usize::try_from((today - day).num_days()) else { continue };
+        if ago < days {
+            counts[days - 1 - ago] += 1;
+        }
+    }
+    counts
+}
+
+// the daemon sends the color the config gives the phase, there is none while idle
+fn phase_color(snapshot: &Snapshot) -> Color {
+    match snapshot.color {
+        Some(rgb) => Color::Rgb(rgb.0, rgb.1, rgb.2),
+        None => Color::DarkGray,
+    }
+}
+
+fn ratio(done: u64, total: u64) -> f64 {
+    if total == 0 { 0.0 } else { (done as f64 / total as f64).min(1.0) }
+}
+
+struct App {
+    client: Client,
+    snapshot: Snapshot,
+    /// the sparkline, reloaded whenever the count of today moves
+    days: Vec<u64>,
+    loaded_today: Option<u64>,
+    picker: Option<(Vec<Task>, ListState)>,
+    /// the last thing that went wrong, shown instead of the help
+    error: Option<String>,
+}
+
+impl App {
+    fn send(&mut self, command: &[u8]) {
+        self.error = self.client.send(command).err();
+    }
+
+    fn reload_history(&mut self) {
+        if self.loaded_today == Some(self.snapshot.today) {
+            return;
+        }
+        self.loaded_today = Some(self.snapshot.today);
+        match history::path().map(|path| history::read(&path)).unwrap_or(Ok(vec![])) {
+            Ok(records) => self.days = daily_counts(&records, Local::now().date_naive(), DAYS),
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    fn open_picker(&mut self) {
+        let list = tasks::path().map(|path| TaskList::load(&path)).unwrap_or(Ok(TaskList::default()));
+        match list {
+            Ok(list) => {
+                let open: Vec<Task> = list.tasks.into_iter().filter(|t| !t.done).collect();
+                if open.is_empty() {
+                    self.error = Some(String::from("no open task, add one with `comodo task add`"));
+                } else {
+                    self.picker = Some((open, ListState::default().with_selected(Some(0))));
+                }
+            },
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    /// `false` once the user is done
+    fn key(&mut self, code: KeyCode) -> bool {
+        if let Some((open, state)) = &mut self.picker {
+            match code {
+                KeyCode::Up | KeyCode::Char('k') => state.select_previous(),
+                KeyCode::Down | KeyCode::Char('j') => state.select_next(),
+                KeyCode::Enter => {
+                    let task = state.selected().and_then(|i| open.get(i)).cloned();
+                    self.picker = None;
+                    if let Some(task) = task {
+                        let lines = [task.name].into_iter().chain(task.tags).collect::<Vec<_>>().join("\n");
+                        self.send(&[&TASK[..], lines.as_bytes()].concat());
+                    }
+                },
+                KeyCode::Esc | KeyCode::Char('q') => self.picker = None,
+                _ => {},
+            }
+            return true;
+        }
+        match input(code, &self.snapshot) {
+            Some(Input::Quit) => return false,
+            Some(Input::Pick) => self.open_picker(),
+            Some(Input::Send(command)) => self.send(&command),
+            None => {},
+        }
+        true
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let snapshot = &self.snapshot;
+        let color = phase_color(snapshot);
+        let [title, clock, phase, session, info, sparkline, _, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(big::HEIGHT as u16 + 2),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(1),
+            Constraint::Length(5),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ]).areas(frame.area());
+
+        let heading = match snapshot.phase {
+            Some(phase) if snapshot.running => format!("{} {}/{}{}", phase.name(), snapshot.cycle, snapshot.total,
+                                                       if snapshot.paused { " (paused)" } else { "" }),
+            _ => String::from("no session running, start one with `comodo start`"),
+        };
+        let heading = match snapshot.timer() {
+            DEFAULT_TIMER => heading,
+            timer => format!("{}: {}", timer, heading),
+        };
+        frame.render_widget(Paragraph::new(heading).alignment(Alignment::Center)
+                            .style(Style::default().fg(color).add_modifier(Modifier::BOLD)), title);
+
+        let digits: Vec<Line> = big::lines(&as_time(snapshot.remaining()), '█', 1).into_iter().map(Line::from).collect();
+        frame.render_widget(Paragraph::new(digits).alignment(Alignment::Center)
+                            .style(Style::default().fg(color)).block(Block::bordered()), clock);
+
+        frame.render_widget(Gauge::default()
+                            .block(Block::bordered().title("phase"))
+                            .gauge_style(Style::default().fg(color))
+                            .ratio(ratio(snapshot.elapsed, snapshot.length))
+                            .label(format!("{} / {}", as_time(snapshot.elapsed), as_time(snapshot.length))), phase);
+        frame.render_widget(Gauge::default()
+                            .block(Block::bordered().title("session"))
+                            .gauge_style(Style::default().fg(Color::Cyan))
+                            .ratio(ratio(snapshot.session_elapsed, snapshot.session_length))
+                            .label(format!("{} / {}", as_time(snapshot.session_elapsed), as_time(snapshot.session_length))), session);
+
+        let task = snapshot.task.clone().unwrap_or(String::from("-"));
+        let tags: String = snapshot.tags.iter().map(|t| format!(" +{}", t)).collect();
+        frame.render_widget(Paragraph::new(format!("task: {}{}   today: {}   interruptions: {}",
+                                                   task, tags, snapshot.today, snapshot.interruptions)), info);
+
+        frame.render_widget(Sparkline::default()
+                            .block(Block::bordered().title(format!("last {} days", DAYS)))
+                            .style(Style::default().fg(Color::Red))
+                            .data(&self.days), sparkline);
+
+        match &self.error {
+            Some(e) => frame.render_widget(Paragraph::new(e.as_str()).style(Style::default().fg(Color::Yellow)), help),
+            None => frame.render_widget(Paragraph::new(HELP).style(Style::default().fg(Color::DarkGray)), help),
+        }
+
+        if let Some((open, state)) = &mut self.picker {
+            let area = popup(frame.area(), open.len() as u16 + 2);
+            let items: Vec<String> = open.iter().map(|t| format!("{:>3} {}", t.id, t.name)).collect();
+            frame.render_widget(Clear, area);
+            frame.render_stateful_widget(List::new(items)
+                                         .block(Block::bordered().title("switch to"))
+                                         .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+                                         area, state);
+        }
+    }
+}
+
+/// a box of `height` rows in the middle of `area`
+fn popup(area: Rect, height: u16) -> Rect {
+    let width = area.width.min(50);
+    let height = height.min(area.height);
+    Rect::new(area.x + (area.width - width) / 2, area.y + (area.height - height) / 2, width, height)
+}
+
+fn follow(client: &Client) -> Result<Receiver<Snapshot>, String> {
+    let snapshots = client.subscribe()?;
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for snapshot in snapshots {
+            if sender.send(snapshot).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App, snapshots: Receiver<Snapshot>) -> Result<(), String> {
+    loop {
+        loop {
+            match snapshots.try_recv() {
+                Ok(snapshot) => app.snapshot = snapshot,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err(String::from("the daemon went away")),
+            }
+        }
+        app.reload_history();
+        terminal.draw(|frame| app.draw(frame)).map_err(|e| e.to_string())?;
+
+        if event::poll(Duration::from_millis(100)).map_err(|e| e.to_string())? {
+            if let Event::Key(key) = event::read().map_err(|e| e.to_string())? {
+                if key.kind == KeyEventKind::Press && !app.key(key.code) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Full screen view of the timer of `client`, until `q`
+pub fn run(client: Client) -> Result<(), String> {
+    let snapshots = follow(&client)?;
+    let mut app = App {
+        client,
+        snapshot: Snapshot::default(),
+        days: vec![0; DAYS],
+        loaded_today: None,
+        picker: None,
+        error: None,
+    };
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app, snapshots);
+    ratatui::restore();
+    result
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use chrono::NaiveDate;
+    #[allow(unused_imports)]
+    use ratatui::crossterm::event::KeyCode;
+    #[allow(unused_imports)]
+    use crate::{history::{Outcome, Record}, protocol::{PAUSE, RESUME, SKIP, STOP}, session::Phase, snapshot::Snapshot};
+    #[allow(unused_imports)]
+    use super::{daily_counts, input, Input};
+
+    #[test]
+    fn input_test() {
+        let running = Snapshot { running: true, ..Default::default() };
+        let paused = Snapshot { paused: true, ..running.clone() };
+        assert_eq!(input(KeyCode::Char(' '), &running), Some(Input::Send(PAUSE.to_vec())));
+        assert_eq!(input(KeyCode::Char(' '), &paused), Some(Input::Send(RESUME.to_vec())));
+        assert_eq!(input(KeyCode::Char('s'), &running), Some(Input::Send(SKIP.to_vec())));
+        assert_eq!(input(KeyCode::Char('x'), &running), Some(Input::Send(STOP.to_vec())));
+        assert_eq!(input(KeyCode::Char('e'), &running), Some(Input::Send(vec![7, 0, 0, 0, 0, 0, 0, 1, 44])));
+        assert_eq!(input(KeyCode::Char('t'), &running), Some(Input::Pick));
+        assert_eq!(input(KeyCode::Esc, &running), Some(Input::Quit));
+        assert_eq!(input(KeyCode::Char('z'), &running), None);
+    }
+
+    #[test]
+    fn daily_counts_test() {
+        let record = |start: &str, phase, outcome| Record { phase, outcome, ..Record::sample(start) };
+        let records = vec![
+            record("2026-10-19T09:00:00", Phase::Focus, Outcome::Completed),
+            record("2026-10-19T10:00:00", Phase::Focus, Outcome::Completed),
+            record("2026-10-19T10:30:00", Phase::Focus, Outcome::Skipped),
+            record("2026-10-19T10:25:00", Phase::ShortBreak, Outcome::Completed),
+            record("2026-10-17T09:00:00", Phase::Focus, Outcome::Completed),
+            record("2026-09-01T09:00:00", Phase::Focus, Outcome::Completed),
+        ];
+        let records: Vec<Record> = records.into_iter().map(|r| {
+            let start = chrono::NaiveDateTime::parse_from_str(&r.start, "%Y-%m-%dT%H:%M:%S").unwrap()
+                .and_local_timezone(chrono::Local).unwrap().to_rfc3339();
+            Record { start, ..r }
+        }).collect();
+        let today = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        assert_eq!(daily_counts(&records, today, 4), vec![0, 1, 0, 2]);
+    }
+}